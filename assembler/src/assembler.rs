@@ -1,54 +1,191 @@
+use crate::errors::AsmError;
 use crate::{code::*, parser::*, symbol_table::SymbolTable, utils::contains_alphabetic};
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufRead, Write};
 
-pub fn assemble(input: &str, output: &str) -> std::io::Result<()> {
-    let instructions = parse_file(input)?;
+// Name used in error locations when the source doesn't come from a file
+const NO_FILE: &str = "<input>";
+
+// Extra files written alongside the `.hack` output
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub listing: Option<String>, // ROM address, binary word and source line of each instruction
+    pub symbols: Option<String>, // labels with their ROM address, variables with their RAM address
+}
+
+// Assembles `input` into `output`.
+// Every invalid instruction is reported, the output file is only written if there is none.
+pub fn assemble(input: &str, output: &str) -> Result<(), Vec<AsmError>> {
+    assemble_with_options(input, output, &Options::default())
+}
+
+// Assembles `input` into `output` and writes the listing and symbol map asked in `options`
+pub fn assemble_with_options(input: &str, output: &str, options: &Options) -> Result<(), Vec<AsmError>> {
+    let instructions = parse_file(input).map_err(|e| vec![AsmError::Io(input.to_string(), e)])?;
+    let (words, symbol_table) = translate(&instructions)?;
+
+    write_file(output, |file| {
+        for word in &words {
+            writeln!(file, "{:016b}", word)?;
+        }
+        Ok(())
+    })?;
+
+    if let Some(listing) = &options.listing {
+        write_file(listing, |file| write_listing(file, &instructions, &words))?;
+    }
+    if let Some(symbols) = &options.symbols {
+        write_file(symbols, |file| write_symbols(file, &symbol_table))?;
+    }
+
+    Ok(())
+}
+
+// Assembles the file `input` into binary words, without writing any file
+pub fn assemble_file(input: &str) -> Result<Vec<u16>, Vec<AsmError>> {
+    let instructions = parse_file(input).map_err(|e| vec![AsmError::Io(input.to_string(), e)])?;
+    let (words, _) = translate(&instructions)?;
+    Ok(words)
+}
+
+// Assembles Hack assembly source code into binary words, without touching the filesystem
+pub fn assemble_str(source: &str) -> Result<Vec<u16>, Vec<AsmError>> {
+    let instructions =
+        parse(source.as_bytes(), NO_FILE).map_err(|e| vec![AsmError::Io(NO_FILE.to_string(), e)])?;
+    let (words, _) = translate(&instructions)?;
+    Ok(words)
+}
+
+// Assembles Hack assembly read from `reader` and writes the `.hack` lines into `writer`
+pub fn assemble_stream<R: BufRead, W: Write>(reader: R, mut writer: W) -> Result<(), Vec<AsmError>> {
+    let instructions =
+        parse(reader, NO_FILE).map_err(|e| vec![AsmError::Io(NO_FILE.to_string(), e)])?;
+    let (words, _) = translate(&instructions)?;
+
+    for word in &words {
+        writeln!(writer, "{:016b}", word).map_err(|e| vec![AsmError::Io("<output>".to_string(), e)])?;
+    }
+    Ok(())
+}
+
+// Translates instructions into binary words, returns the symbol table built along the way
+fn translate(instructions: &Vec<Instruction>) -> Result<(Vec<u16>, SymbolTable), Vec<AsmError>> {
     let mut symbol_table = SymbolTable::new();
 
     // First pass, records labels
-    first_pass(&instructions, &mut symbol_table);
+    first_pass(instructions, &mut symbol_table);
 
     // Second pass, translates into binary
-    let mut file = File::create(output)?;
-    for instruction in &instructions {
-        let binary = match instruction {
-            Instruction::A(value) => {
-                let binary_string: String;
+    let mut words = Vec::new();
+    let mut errors = Vec::new();
+    for instruction in instructions {
+        let span = instruction.span();
+        let location = |column: usize| span.location(column);
+        let start = span.start();
+
+        let word = match instruction {
+            Instruction::A(value, _) => {
                 // Check if value == @R0 or ADDRESS
-                let is_alphabetic = contains_alphabetic(&value);
-                if is_alphabetic == true {
-                    let value = symbol_table.get_address(&value);
-                    binary_string = format!("0{:015b}", value);
+                let is_alphabetic = contains_alphabetic(value);
+                if is_alphabetic {
+                    symbol_table.get_address(value)
                 } else {
-                    binary_string = format!("0{:015b}", value.parse::<u16>().unwrap());
+                    match value.parse::<u16>() {
+                        Ok(value) if value <= 32767 => value,
+                        _ => {
+                            errors.push(AsmError::InvalidAddress(location(start + 1), value.clone()));
+                            continue;
+                        }
+                    }
                 }
-                binary_string
             }
-            Instruction::C { dest, comp, jump } => {
-                format!(
-                    "111{}{}{}",
-                    comp_to_bin(&comp),
-                    dest_to_bin(&dest),
-                    jump_to_bin(&jump)
-                )
+            Instruction::C { dest, comp, jump, .. } => {
+                let dest_len = dest.as_ref().map_or(0, |d| d.len() + 1);
+                let comp_bin = comp_to_bin(comp);
+                let dest_bin = dest_to_bin(dest);
+                let jump_bin = jump_to_bin(jump);
+
+                if dest_bin.is_none() {
+                    errors.push(AsmError::UnknownDest(location(start), dest.clone().unwrap_or_default()));
+                }
+                if comp_bin.is_none() {
+                    errors.push(AsmError::UnknownComp(location(start + dest_len), comp.clone()));
+                }
+                if jump_bin.is_none() {
+                    errors.push(AsmError::UnknownJump(
+                        location(start + dest_len + comp.len() + 1),
+                        jump.clone().unwrap_or_default(),
+                    ));
+                }
+
+                match (comp_bin, dest_bin, jump_bin) {
+                    // 111a cccc ccdd djjj
+                    (Some(c), Some(d), Some(j)) => 0b111 << 13 | c << 6 | d << 3 | j,
+                    _ => continue,
+                }
             }
             _ => continue,
         };
-        writeln!(file, "{}", binary)?;
+        words.push(word);
     }
 
-    Ok(())
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok((words, symbol_table))
 }
 
 fn first_pass(instructions: &Vec<Instruction>, symbol_table: &mut SymbolTable) {
   let mut count: u16 = 0;
     for instruction in instructions {
         match instruction {
-            Instruction::Label(label) => {
+            Instruction::Label(label, _) => {
                 symbol_table.add_label(label, count);
             },
             _ => count += 1,
         };
     }
 }
+
+// Writes one line per instruction: ROM address, binary word, source line
+// Labels don't take ROM space, only their source line is written.
+fn write_listing(file: &mut File, instructions: &Vec<Instruction>, words: &[u16]) -> std::io::Result<()> {
+    let mut words = words.iter().enumerate();
+    for instruction in instructions {
+        let span = instruction.span();
+        match instruction {
+            Instruction::Label(_, _) => {
+                writeln!(file, "{:>5}  {:16}  {:>5}: {}", "", "", span.line, span.text)?
+            }
+            _ => {
+                let (address, word) = words.next().unwrap();
+                writeln!(file, "{:>5}  {:016b}  {:>5}: {}", address, word, span.line, span.text)?
+            }
+        }
+    }
+    Ok(())
+}
+
+// Writes labels with their ROM address then variables with their RAM address
+fn write_symbols(file: &mut File, symbol_table: &SymbolTable) -> std::io::Result<()> {
+    writeln!(file, "// Labels (ROM address)")?;
+    for (label, address) in symbol_table.labels() {
+        writeln!(file, "{} {}", label, address)?;
+    }
+    writeln!(file, "// Variables (RAM address)")?;
+    for (variable, address) in symbol_table.variables() {
+        writeln!(file, "{} {}", variable, address)?;
+    }
+    Ok(())
+}
+
+// Creates `path` and fills it with `write`
+fn write_file<F>(path: &str, write: F) -> Result<(), Vec<AsmError>>
+where
+    F: FnOnce(&mut File) -> std::io::Result<()>,
+{
+    File::create(path)
+        .and_then(|mut file| write(&mut file))
+        .map_err(|e| vec![AsmError::Io(path.to_string(), e)])
+}
@@ -1,61 +1,92 @@
-pub fn comp_to_bin(comp: &str) -> &'static str {
-  match comp {
-      "0" => "0101010",
-      "1" => "0111111",
-      "-1" => "0111010",
-      "D" => "0001100",
-      "A" => "0110000",
-      "M" => "1110000",
-      "!D" => "0001101",
-      "!A" => "0110001",
-      "!M" => "1110001",
-      "-D" => "0001111",
-      "-A" => "0110011",
-      "-M" => "1110011",
-      "D+1" => "0011111",
-      "A+1" => "0110111",
-      "M+1" => "1110111",
-      "D-1" => "0001110",
-      "A-1" => "0110010",
-      "M-1" => "1110010",
-      "D+A" => "0000010",
-      "D+M" => "1000010",
-      "D-A" => "0010011",
-      "D-M" => "1010011",
-      "A-D" => "0000111",
-      "M-D" => "1000111",
-      "D&A" => "0000000",
-      "D&M" => "1000000",
-      "D|A" => "0010101",
-      "D|M" => "1010101",
-      _ => panic!("Instruction de calcul inconnue: {}", comp),
-  }
+pub fn comp_to_bin(comp: &str) -> Option<u16> {
+  let bin = match comp {
+      "0" => 0b0101010,
+      "1" => 0b0111111,
+      "-1" => 0b0111010,
+      "D" => 0b0001100,
+      "A" => 0b0110000,
+      "M" => 0b1110000,
+      "!D" => 0b0001101,
+      "!A" => 0b0110001,
+      "!M" => 0b1110001,
+      "-D" => 0b0001111,
+      "-A" => 0b0110011,
+      "-M" => 0b1110011,
+      "D+1" => 0b0011111,
+      "A+1" => 0b0110111,
+      "M+1" => 0b1110111,
+      "D-1" => 0b0001110,
+      "A-1" => 0b0110010,
+      "M-1" => 0b1110010,
+      "D+A" => 0b0000010,
+      "D+M" => 0b1000010,
+      "D-A" => 0b0010011,
+      "D-M" => 0b1010011,
+      "A-D" => 0b0000111,
+      "M-D" => 0b1000111,
+      "D&A" => 0b0000000,
+      "D&M" => 0b1000000,
+      "D|A" => 0b0010101,
+      "D|M" => 0b1010101,
+      _ => return None,
+  };
+  Some(bin)
+}
+
+pub fn dest_to_bin(dest: &Option<String>) -> Option<u16> {
+  let bin = match dest.as_deref() {
+      None => 0b000,
+      Some("M") => 0b001,
+      Some("D") => 0b010,
+      Some("MD") => 0b011,
+      Some("A") => 0b100,
+      Some("AM") => 0b101,
+      Some("AD") => 0b110,
+      Some("AMD") => 0b111,
+      _ => return None,
+  };
+  Some(bin)
+}
+
+pub fn jump_to_bin(jump: &Option<String>) -> Option<u16> {
+  let bin = match jump.as_deref() {
+      None => 0b000,
+      Some("JGT") => 0b001,
+      Some("JEQ") => 0b010,
+      Some("JGE") => 0b011,
+      Some("JLT") => 0b100,
+      Some("JNE") => 0b101,
+      Some("JLE") => 0b110,
+      Some("JMP") => 0b111,
+      _ => return None,
+  };
+  Some(bin)
+}
+
+const COMPS: [&str; 28] = [
+  "0", "1", "-1", "D", "A", "M", "!D", "!A", "!M", "-D", "-A", "-M", "D+1", "A+1", "M+1",
+  "D-1", "A-1", "M-1", "D+A", "D+M", "D-A", "D-M", "A-D", "M-D", "D&A", "D&M", "D|A", "D|M",
+];
+const DESTS: [&str; 7] = ["M", "D", "MD", "A", "AM", "AD", "AMD"];
+const JUMPS: [&str; 7] = ["JGT", "JEQ", "JGE", "JLT", "JNE", "JLE", "JMP"];
+
+// Inverse of `comp_to_bin`: "a c1..c6" bits to mnemonic
+pub fn bin_to_comp(bin: u16) -> Option<&'static str> {
+  COMPS.into_iter().find(|comp| comp_to_bin(comp) == Some(bin))
 }
 
-pub fn dest_to_bin(dest: &Option<String>) -> &'static str {
-  match dest.as_deref() {
-      None => "000",
-      Some("M") => "001",
-      Some("D") => "010",
-      Some("MD") => "011",
-      Some("A") => "100",
-      Some("AM") => "101",
-      Some("AD") => "110",
-      Some("AMD") => "111",
-      _ => panic!("Destination inconnue"),
+// Inverse of `dest_to_bin`, returns "" for a null destination
+pub fn bin_to_dest(bin: u16) -> Option<&'static str> {
+  if bin == 0 {
+    return Some("");
   }
+  DESTS.into_iter().find(|dest| dest_to_bin(&Some(dest.to_string())) == Some(bin))
 }
 
-pub fn jump_to_bin(jump: &Option<String>) -> &'static str {
-  match jump.as_deref() {
-      None => "000",
-      Some("JGT") => "001",
-      Some("JEQ") => "010",
-      Some("JGE") => "011",
-      Some("JLT") => "100",
-      Some("JNE") => "101",
-      Some("JLE") => "110",
-      Some("JMP") => "111",
-      _ => panic!("Saut inconnu"),
+// Inverse of `jump_to_bin`, returns "" for no jump
+pub fn bin_to_jump(bin: u16) -> Option<&'static str> {
+  if bin == 0 {
+    return Some("");
   }
+  JUMPS.into_iter().find(|jump| jump_to_bin(&Some(jump.to_string())) == Some(bin))
 }
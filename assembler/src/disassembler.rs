@@ -0,0 +1,121 @@
+use crate::code::{bin_to_comp, bin_to_dest, bin_to_jump};
+use crate::errors::{AsmError, Location};
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::Write;
+
+// Translates a `.hack` file back into Hack assembly.
+// Jump targets get a `(L<address>)` label so the output reassembles bit-identically.
+pub fn disassemble(input: &str, output: &str) -> Result<(), Vec<AsmError>> {
+    let source = fs::read_to_string(input).map_err(|e| vec![AsmError::Io(input.to_string(), e)])?;
+
+    // Reads and decodes every binary word
+    let mut instructions = Vec::new();
+    let mut errors = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let location = Location {
+            file: input.to_string(),
+            line: index + 1,
+            column: 1,
+        };
+        match parse_word(line).map(decode) {
+            Some(Some(instruction)) => instructions.push(instruction),
+            Some(None) => errors.push(AsmError::UnknownInstruction(location, line.to_string())),
+            None => errors.push(AsmError::InvalidWord(location, line.to_string())),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let targets = jump_targets(&instructions);
+
+    let io_error = |e| vec![AsmError::Io(output.to_string(), e)];
+    let mut file = File::create(output).map_err(io_error)?;
+    for (address, instruction) in instructions.iter().enumerate() {
+        if targets.contains(&(address as u16)) {
+            writeln!(file, "(L{})", address).map_err(io_error)?;
+        }
+        match instruction {
+            Decoded::A(value) if is_jump_address(&instructions, address) => {
+                writeln!(file, "@L{}", value).map_err(io_error)?
+            }
+            Decoded::A(value) => writeln!(file, "@{}", value).map_err(io_error)?,
+            Decoded::C(code) => writeln!(file, "{}", code).map_err(io_error)?,
+        }
+    }
+    // A jump may target the address right after the last instruction
+    if targets.contains(&(instructions.len() as u16)) {
+        writeln!(file, "(L{})", instructions.len()).map_err(io_error)?;
+    }
+
+    Ok(())
+}
+
+enum Decoded {
+    A(u16),
+    C(String),
+}
+
+// Parses a line of 16 '0' or '1'
+fn parse_word(line: &str) -> Option<u16> {
+    if line.len() != 16 || !line.chars().all(|c| c == '0' || c == '1') {
+        return None;
+    }
+    u16::from_str_radix(line, 2).ok()
+}
+
+// Decodes a binary word into an A or C instruction
+fn decode(word: u16) -> Option<Decoded> {
+    if word & 0x8000 == 0 {
+        return Some(Decoded::A(word));
+    }
+
+    // 111a cccc ccdd djjj
+    if word >> 13 != 0b111 {
+        return None;
+    }
+    let comp = bin_to_comp((word >> 6) & 0x7F)?;
+    let dest = bin_to_dest((word >> 3) & 0x7)?;
+    let jump = bin_to_jump(word & 0x7)?;
+
+    let mut code = String::new();
+    if !dest.is_empty() {
+        code.push_str(dest);
+        code.push('=');
+    }
+    code.push_str(comp);
+    if !jump.is_empty() {
+        code.push(';');
+        code.push_str(jump);
+    }
+    Some(Decoded::C(code))
+}
+
+// An A instruction holds a jump address when it is followed by a jump
+fn is_jump_address(instructions: &[Decoded], address: usize) -> bool {
+    match (&instructions[address], instructions.get(address + 1)) {
+        (Decoded::A(value), Some(Decoded::C(code))) => {
+            code.contains(';') && *value as usize <= instructions.len()
+        }
+        _ => false,
+    }
+}
+
+// Collects every ROM address used as a jump target
+fn jump_targets(instructions: &[Decoded]) -> BTreeSet<u16> {
+    let mut targets = BTreeSet::new();
+    for (address, instruction) in instructions.iter().enumerate() {
+        if let Decoded::A(value) = instruction
+            && is_jump_address(instructions, address)
+        {
+            targets.insert(*value);
+        }
+    }
+    targets
+}
@@ -0,0 +1,54 @@
+use std::{error::Error, fmt, io};
+
+// Position of an error in the source file (line and column start at 1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug)]
+pub enum AsmError {
+    Io(String, io::Error), // file, cause
+    UnknownComp(Location, String), // location, comp
+    UnknownDest(Location, String), // location, dest
+    UnknownJump(Location, String), // location, jump
+    InvalidAddress(Location, String), // location, value
+    InvalidWord(Location, String), // location, word
+    UnknownInstruction(Location, String), // location, word
+}
+
+impl Error for AsmError {}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsmError::Io(file, err) => write!(f, "{}: {}", file, err),
+            AsmError::UnknownComp(loc, comp) => {
+                write!(f, "{}: unknown comp instruction `{}`", loc, comp)
+            }
+            AsmError::UnknownDest(loc, dest) => {
+                write!(f, "{}: unknown destination `{}`", loc, dest)
+            }
+            AsmError::UnknownJump(loc, jump) => write!(f, "{}: unknown jump `{}`", loc, jump),
+            AsmError::InvalidAddress(loc, value) => write!(
+                f,
+                "{}: invalid address `{}` (expected a value between 0 and 32767)",
+                loc, value
+            ),
+            AsmError::InvalidWord(loc, word) => {
+                write!(f, "{}: `{}` is not a 16-bit binary word", loc, word)
+            }
+            AsmError::UnknownInstruction(loc, word) => {
+                write!(f, "{}: `{}` is not a valid Hack instruction", loc, word)
+            }
+        }
+    }
+}
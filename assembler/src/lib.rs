@@ -3,5 +3,10 @@ mod code;
 mod symbol_table;
 mod assembler;
 mod utils;
+mod errors;
+mod disassembler;
 
-pub use assembler::assemble;
\ No newline at end of file
+pub use assembler::{assemble, assemble_file, assemble_str, assemble_stream, assemble_with_options, Options};
+pub use disassembler::disassemble;
+pub use errors::{AsmError, Location};
+pub use parser::{parse, parse_file, Instruction, Span};
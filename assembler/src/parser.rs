@@ -1,44 +1,82 @@
+use crate::errors::Location;
 use std::fs::File;
 use std::io::{self, BufRead};
 use std::path::Path;
 
+// Where an instruction comes from in the source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub file: String,
+    pub line: usize,  // starts at 1
+    pub text: String, // original source line
+}
+
 #[derive(Debug, Clone)]
 pub enum Instruction {
-    A(String), // @value
-    C { dest: Option<String>, comp: String, jump: Option<String> }, // dest=comp;jump
-    Label(String), // (LABEL)
+    A(String, Span), // @value
+    C { dest: Option<String>, comp: String, jump: Option<String>, span: Span }, // dest=comp;jump
+    Label(String, Span), // (LABEL)
+}
+
+impl Span {
+    // Column of the first non-whitespace character of the line
+    pub fn start(&self) -> usize {
+        self.text.len() - self.text.trim_start().len() + 1
+    }
+
+    // Location of the given column of the line
+    pub fn location(&self, column: usize) -> Location {
+        Location {
+            file: self.file.clone(),
+            line: self.line,
+            column,
+        }
+    }
+}
+
+impl Instruction {
+    pub fn span(&self) -> &Span {
+        match self {
+            Instruction::A(_, span) => span,
+            Instruction::C { span, .. } => span,
+            Instruction::Label(_, span) => span,
+        }
+    }
 }
 
 pub fn parse_file(filename: &str) -> io::Result<Vec<Instruction>> {
     let path = Path::new(filename);
-    let file = File::open(&path)?;
-    let reader = io::BufReader::new(file);
+    let file = File::open(path)?;
+    parse(io::BufReader::new(file), filename)
+}
+
+// Parses Hack assembly read from `reader`, `filename` is only used in spans
+pub fn parse<R: BufRead>(reader: R, filename: &str) -> io::Result<Vec<Instruction>> {
     let mut instructions = Vec::new();
 
-    for line in reader.lines() {
-        let line = line?;
-        let line = line.trim();
+    for (index, source) in reader.lines().enumerate() {
+        let source = source?;
+        // Remove comments, even at the end of an instruction: `D=M // x` is `D=M`
+        let line = source.split("//").next().unwrap_or("").trim();
+        let span = Span {
+            file: filename.to_string(),
+            line: index + 1,
+            text: source.clone(),
+        };
 
         // Remove empty lines
-        if line.is_empty() { 
-            continue;
-        } 
-        // Remove comments
-        else if line.starts_with("//") { 
+        if line.is_empty() {
             continue;
-        } 
-        // A instruction: @R0
-        else if line.starts_with("@R") {
-            instructions.push(Instruction::A(line[2..].to_string()));
-        } 
-        // A instruction: @0
-        else if line.starts_with('@') {
-            instructions.push(Instruction::A(line[1..].to_string()));
-        } 
+        }
+        // A instruction: @0, @R0 or @LABEL. The symbol is kept whole, R0-R15 are predefined
+        // symbols and other names starting with R (`@RET`) are labels or variables
+        else if let Some(value) = line.strip_prefix('@') {
+            instructions.push(Instruction::A(value.to_string(), span));
+        }
         // Label instruction
         else if line.starts_with("(") && line.ends_with(")") {
-            instructions.push(Instruction::Label(line[1..line.len() - 1].to_string()));
-        } 
+            instructions.push(Instruction::Label(line[1..line.len() - 1].to_string(), span));
+        }
         // C instruction
         else {
             let mut parts = line.split('=');
@@ -56,11 +94,9 @@ pub fn parse_file(filename: &str) -> io::Result<Vec<Instruction>> {
             let comp = parts.next().unwrap().to_string();
             let jump = parts.next().map(|j| j.to_string());
 
-            instructions.push(Instruction::C { dest, comp, jump });
+            instructions.push(Instruction::C { dest, comp, jump, span });
         }
     }
 
     Ok(instructions)
 }
-
-
@@ -4,6 +4,8 @@ use std::collections::HashMap;
 pub struct SymbolTable {
     table: HashMap<String, u16>,
     next_var_address: u16,
+    labels: Vec<(String, u16)>,    // user labels and their ROM address, in declaration order
+    variables: Vec<(String, u16)>, // variables and their RAM address, in allocation order
 }
 
 impl SymbolTable {
@@ -13,14 +15,25 @@ impl SymbolTable {
         for i in 0..16 {
             table.insert(format!("R{}", i), i);
         }
+        table.insert("SP".to_string(), 0);
+        table.insert("LCL".to_string(), 1);
+        table.insert("ARG".to_string(), 2);
+        table.insert("THIS".to_string(), 3);
+        table.insert("THAT".to_string(), 4);
         table.insert("SCREEN".to_string(), 16384);
         table.insert("KBD".to_string(), 24576);
 
-        SymbolTable { table, next_var_address: 16 } // 16 is the RAM starting address.
+        SymbolTable {
+            table,
+            next_var_address: 16, // 16 is the RAM starting address.
+            labels: Vec::new(),
+            variables: Vec::new(),
+        }
     }
 
     pub fn add_label(&mut self, label: &str, address: u16) {
         self.table.insert(label.to_string(), address);
+        self.labels.push((label.to_string(), address));
     }
 
     pub fn get_address(&mut self, symbol: &str) -> u16 {
@@ -29,6 +42,7 @@ impl SymbolTable {
         }
         let addr = self.next_var_address;
         self.table.insert(symbol.to_string(), addr);
+        self.variables.push((symbol.to_string(), addr));
         self.next_var_address += 1;
         addr
     }
@@ -36,4 +50,12 @@ impl SymbolTable {
     pub fn get_last_ram_address(&self) -> u16 {
       self.next_var_address
     }
+
+    pub fn labels(&self) -> &[(String, u16)] {
+        &self.labels
+    }
+
+    pub fn variables(&self) -> &[(String, u16)] {
+        &self.variables
+    }
 }
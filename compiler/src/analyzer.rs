@@ -1,45 +1,109 @@
 use std::fs;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use crate::tokenizer::tokenize;
+use crate::ast::Class;
+use crate::checker::{check, check_program, Program};
 use crate::compilation_engine::CompilationEngine;
+use crate::errors::{Diagnostic, JackError};
+use crate::parser::parse_source;
+use crate::xml_writer::write_xml;
 
-pub fn analyze(source: &str) -> Result<(), io::Error> {
+// Compiler settings
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    // Adds category, index and usage attributes to the identifiers of the XML output
+    pub annotate_identifiers: bool,
+    // Checks the classes together: calls between classes, to the OS and the classes used as types
+    pub whole_program: bool,
+}
+
+// A parsed `.jack` file
+struct SourceFile {
+    path: PathBuf,
+    source: String,
+    class: Class,
+}
+
+// Compiles a `.jack` file or every `.jack` file of a folder
+// into `<name>.xml` (parse tree) and `<name>.vm` next to the sources.
+// Reports the errors of every file.
+pub fn analyze(source: &str) -> Result<(), Vec<JackError>> {
+    analyze_with_options(source, &Options::default())
+}
+
+pub fn analyze_with_options(source: &str, options: &Options) -> Result<(), Vec<JackError>> {
     let path = Path::new(source);
+    let io_error = |e| vec![JackError::Io(source.to_string(), e)];
+    let mut paths = Vec::new();
 
     if path.is_file() {
         // C'est un fichier `.jack`, on le traite directement
         if let Some(ext) = path.extension() {
             if ext == "jack" {
-                process_file(path)?;
+                paths.push(path.to_path_buf());
             }
         }
     } else if path.is_dir() {
         // C'est un dossier, on traite chaque fichier `.jack`
-        for entry in fs::read_dir(path)? {
-            let entry = entry?;
+        for entry in fs::read_dir(path).map_err(io_error)? {
+            let entry = entry.map_err(io_error)?;
             let file_path = entry.path();
 
             if let Some(ext) = file_path.extension() {
                 if ext == "jack" {
-                    process_file(&file_path)?;
+                    paths.push(file_path);
                 }
             }
         }
     } else {
-        return Err(io::Error::new(io::ErrorKind::NotFound, "Invalid source path"));
+        return Err(io_error(io::Error::new(io::ErrorKind::NotFound, "Invalid source path")));
     }
 
-    Ok(())
+    // Every class is parsed before it is checked
+    let mut errors = Vec::new();
+    let mut files = Vec::new();
+    for path in paths {
+        read_class(path).map_or_else(|e| errors.extend(e), |file| files.push(file));
+    }
+
+    // The program is only known when every file parses
+    let program = (options.whole_program && errors.is_empty()).then(|| Program::new(files.iter().map(|f| &f.class)));
+    for file in &files {
+        let diagnostics = match &program {
+            Some(program) => check_program(program, &file.class),
+            None => check(&file.class),
+        };
+        compile_file(file, diagnostics, options).unwrap_or_else(|e| errors.extend(e));
+    }
+
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
 }
 
-fn process_file(file_path: &Path) -> Result<(), io::Error> {
-    // 1. Tokenizer : handle tokens
-    let tokens = tokenize(file_path.to_str().unwrap())?;
+// 1. Tokenizer and parser : syntax tree
+fn read_class(path: PathBuf) -> Result<SourceFile, Vec<JackError>> {
+    let file = path.to_string_lossy().into_owned();
+    let source = fs::read_to_string(&path).map_err(|e| vec![JackError::Io(file.clone(), e)])?;
+    let class = parse_source(&source).map_err(|diagnostics| JackError::from_diagnostics(&file, &source, diagnostics))?;
+    Ok(SourceFile { path, source, class })
+}
+
+// 2. Semantic checks, then the backends : xml and vm files
+fn compile_file(file: &SourceFile, mut diagnostics: Vec<Diagnostic>, options: &Options) -> Result<(), Vec<JackError>> {
+    let name = file.path.to_string_lossy();
+    let io_error = |e| vec![JackError::Io(name.to_string(), e)];
 
-    // 2. CompilationEngine : handle xml file
-    let _engine = CompilationEngine::new(tokens, file_path.to_str().unwrap())?;
+    let vm = CompilationEngine::compile(&file.class).unwrap_or_else(|errors| {
+        diagnostics.extend(errors);
+        String::new()
+    });
+    if !diagnostics.is_empty() {
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.span.line, diagnostic.span.column));
+        return Err(JackError::from_diagnostics(&name, &file.source, diagnostics));
+    }
+    let xml = write_xml(&file.class, options.annotate_identifiers);
 
+    fs::write(file.path.with_extension("xml"), xml).map_err(io_error)?;
+    fs::write(file.path.with_extension("vm"), vm).map_err(io_error)?;
     Ok(())
 }
@@ -0,0 +1,238 @@
+// Abstract syntax tree of a Jack class
+
+use crate::tokenizer::Span;
+
+// A name and where it is written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identifier {
+    pub name: String,
+    pub span: Span,
+}
+
+// 'class' className '{' classVarDec* subroutineDec* '}'
+#[derive(Debug, Clone, PartialEq)]
+pub struct Class {
+    pub name: Identifier,
+    pub vars: Vec<ClassVarDec>,
+    pub subroutines: Vec<Subroutine>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClassVarKind {
+    Static,
+    Field,
+}
+
+impl ClassVarKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClassVarKind::Static => "static",
+            ClassVarKind::Field => "field",
+        }
+    }
+}
+
+// ('static' | 'field') type varName (',' varName)* ';'
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassVarDec {
+    pub kind: ClassVarKind,
+    pub type_name: Type,
+    pub names: Vec<Identifier>,
+}
+
+// int, char, boolean, a class name, or void for subroutines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    Int,
+    Char,
+    Boolean,
+    Void,
+    Class(String),
+}
+
+impl Type {
+    pub fn name(&self) -> &str {
+        match self {
+            Type::Int => "int",
+            Type::Char => "char",
+            Type::Boolean => "boolean",
+            Type::Void => "void",
+            Type::Class(name) => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubroutineKind {
+    Constructor,
+    Function,
+    Method,
+}
+
+impl SubroutineKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubroutineKind::Constructor => "constructor",
+            SubroutineKind::Function => "function",
+            SubroutineKind::Method => "method",
+        }
+    }
+}
+
+// ('constructor' | 'function' | 'method') ('void' | type) subroutineName
+// '(' parameterList ')' '{' varDec* statements '}'
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subroutine {
+    pub kind: SubroutineKind,
+    pub return_type: Type,
+    pub name: Identifier,
+    pub parameters: Vec<Parameter>,
+    pub vars: Vec<VarDec>,
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub type_name: Type,
+    pub name: Identifier,
+}
+
+// 'var' type varName (',' varName)* ';'
+#[derive(Debug, Clone, PartialEq)]
+pub struct VarDec {
+    pub type_name: Type,
+    pub names: Vec<Identifier>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    // 'let' varName ('[' expression ']')? '=' expression ';'
+    Let {
+        name: Identifier,
+        index: Option<Expression>,
+        value: Expression,
+    },
+    // 'if' '(' expression ')' '{' statements '}' ('else' '{' statements '}')?
+    If {
+        condition: Expression,
+        statements: Vec<Statement>,
+        else_statements: Option<Vec<Statement>>,
+    },
+    // 'while' '(' expression ')' '{' statements '}'
+    While {
+        condition: Expression,
+        statements: Vec<Statement>,
+    },
+    // 'do' subroutineCall ';'
+    Do(SubroutineCall),
+    // 'return' expression? ';', with the position of `return`
+    Return(Option<Expression>, Span),
+}
+
+// term (op term)*
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    pub term: Term,
+    pub operations: Vec<(Op, Term)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    IntegerConstant(u16),
+    StringConstant(String),
+    KeywordConstant(KeywordConstant, Span),
+    Variable(Identifier),
+    // varName '[' expression ']'
+    ArrayElement(Identifier, Box<Expression>),
+    // '(' expression ')'
+    Parenthesized(Box<Expression>),
+    Unary(UnaryOp, Box<Term>),
+    Call(SubroutineCall),
+}
+
+// subroutineName '(' expressionList ')'
+// | (className | varName) '.' subroutineName '(' expressionList ')'
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubroutineCall {
+    pub receiver: Option<Identifier>,
+    pub name: Identifier,
+    pub arguments: Vec<Expression>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeywordConstant {
+    True,
+    False,
+    Null,
+    This,
+}
+
+impl KeywordConstant {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeywordConstant::True => "true",
+            KeywordConstant::False => "false",
+            KeywordConstant::Null => "null",
+            KeywordConstant::This => "this",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    And,
+    Or,
+    Lt,
+    Gt,
+    Eq,
+}
+
+impl Op {
+    pub fn from_symbol(symbol: char) -> Option<Op> {
+        match symbol {
+            '+' => Some(Op::Add),
+            '-' => Some(Op::Sub),
+            '*' => Some(Op::Mul),
+            '/' => Some(Op::Div),
+            '&' => Some(Op::And),
+            '|' => Some(Op::Or),
+            '<' => Some(Op::Lt),
+            '>' => Some(Op::Gt),
+            '=' => Some(Op::Eq),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Op::Add => "+",
+            Op::Sub => "-",
+            Op::Mul => "*",
+            Op::Div => "/",
+            Op::And => "&",
+            Op::Or => "|",
+            Op::Lt => "<",
+            Op::Gt => ">",
+            Op::Eq => "=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+impl UnaryOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            UnaryOp::Neg => "-",
+            UnaryOp::Not => "~",
+        }
+    }
+}
@@ -0,0 +1,606 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{
+    Class, Expression, Identifier, KeywordConstant, Statement, Subroutine, SubroutineCall, SubroutineKind, Term,
+    Type,
+};
+use crate::errors::Diagnostic;
+use crate::os::os_classes;
+use crate::symbol_table::{Kind, SymbolTable};
+
+// What a call needs to know about a subroutine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signature {
+    pub kind: SubroutineKind,
+    pub parameters: usize,
+}
+
+// Subroutine signatures of the classes of a program, by class then by name
+#[derive(Debug, Default)]
+pub struct Program {
+    classes: HashMap<String, HashMap<String, Signature>>,
+    // Every class is known, other classes are errors
+    complete: bool,
+}
+
+impl Program {
+    // The OS classes and `classes`, a class of the program replaces the OS class of the same name
+    pub fn new<'c>(classes: impl IntoIterator<Item = &'c Class>) -> Self {
+        let mut program = Program { classes: HashMap::new(), complete: true };
+        for class in &os_classes() {
+            program.add(class);
+        }
+        for class in classes {
+            program.add(class);
+        }
+        program
+    }
+
+    fn add(&mut self, class: &Class) {
+        let subroutines = class
+            .subroutines
+            .iter()
+            .map(|subroutine| {
+                let signature = Signature { kind: subroutine.kind, parameters: subroutine.parameters.len() };
+                (subroutine.name.name.clone(), signature)
+            })
+            .collect();
+        self.classes.insert(class.name.name.clone(), subroutines);
+    }
+
+    pub fn signature(&self, class: &str, name: &str) -> Option<Signature> {
+        self.classes.get(class)?.get(name).copied()
+    }
+
+    pub fn has_class(&self, class: &str) -> bool {
+        self.classes.contains_key(class)
+    }
+}
+
+// Checks how a class uses its variables and subroutines:
+// unknown subroutines, argument counts, methods called without an object,
+// `this` and fields in functions, and return statements.
+// Undefined and duplicate variables are reported by the compilation engine.
+// The class is checked alone, calls to other classes are not checked.
+pub fn check(class: &Class) -> Vec<Diagnostic> {
+    let mut program = Program::default();
+    program.add(class);
+    check_program(&program, class)
+}
+
+// Checks a class of `program`: calls to other classes and to the OS
+// and the classes used as types are checked too
+pub fn check_program(program: &Program, class: &Class) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        class_name: &class.name.name,
+        program,
+        symbols: SymbolTable::new(),
+        diagnostics: Vec::new(),
+    };
+
+    let mut names = HashSet::new();
+    for subroutine in &class.subroutines {
+        let name = &subroutine.name;
+        if !names.insert(&name.name) {
+            checker.error(name, format!("duplicate subroutine `{}`", name.name));
+        }
+    }
+
+    for var in &class.vars {
+        for name in &var.names {
+            checker.check_type(&var.type_name, name);
+        }
+    }
+    checker.symbols.define_class(class);
+    for subroutine in &class.subroutines {
+        checker.check_subroutine(subroutine);
+    }
+    checker.diagnostics
+}
+
+struct Checker<'a> {
+    class_name: &'a str,
+    program: &'a Program,
+    symbols: SymbolTable,
+    diagnostics: Vec<Diagnostic>,
+}
+
+// The subroutine being checked
+struct Context<'a> {
+    subroutine: &'a Subroutine,
+}
+
+impl Context<'_> {
+    fn is_function(&self) -> bool {
+        self.subroutine.kind == SubroutineKind::Function
+    }
+
+    fn name(&self) -> &str {
+        &self.subroutine.name.name
+    }
+}
+
+impl<'a> Checker<'a> {
+    fn error(&mut self, identifier: &Identifier, message: String) {
+        self.diagnostics.push(Diagnostic::new(identifier.span, message));
+    }
+
+    fn check_subroutine(&mut self, subroutine: &'a Subroutine) {
+        self.symbols.define_subroutine(self.class_name, subroutine);
+        let context = Context { subroutine };
+
+        self.check_type(&subroutine.return_type, &subroutine.name);
+        for parameter in &subroutine.parameters {
+            self.check_type(&parameter.type_name, &parameter.name);
+        }
+        for var in &subroutine.vars {
+            for name in &var.names {
+                self.check_type(&var.type_name, name);
+            }
+        }
+
+        // Constructors return the new object
+        let is_class = matches!(&subroutine.return_type, Type::Class(name) if name == self.class_name);
+        if subroutine.kind == SubroutineKind::Constructor && !is_class {
+            let message = format!("constructor `{}` must return `{}`", context.name(), self.class_name);
+            self.error(&subroutine.name, message);
+        }
+
+        self.check_statements(&context, &subroutine.statements);
+
+        if !ends_with_return(&subroutine.statements) {
+            let message = format!("`{}` does not end with a return statement", context.name());
+            self.error(&subroutine.name, message);
+        }
+    }
+
+    // The class of a variable or a subroutine `name` must be known
+    fn check_type(&mut self, type_name: &Type, name: &Identifier) {
+        let Type::Class(class) = type_name else { return };
+        if self.program.complete && !self.program.has_class(class) {
+            self.error(name, format!("unknown class `{}` for `{}`", class, name.name));
+        }
+    }
+
+    fn check_statements(&mut self, context: &Context, statements: &[Statement]) {
+        for statement in statements {
+            match statement {
+                Statement::Let { name, index, value } => {
+                    self.check_variable(context, name);
+                    if let Some(index) = index {
+                        self.check_expression(context, index);
+                    }
+                    self.check_expression(context, value);
+                }
+                Statement::If { condition, statements, else_statements } => {
+                    self.check_expression(context, condition);
+                    self.check_statements(context, statements);
+                    if let Some(else_statements) = else_statements {
+                        self.check_statements(context, else_statements);
+                    }
+                }
+                Statement::While { condition, statements } => {
+                    self.check_expression(context, condition);
+                    self.check_statements(context, statements);
+                }
+                Statement::Do(call) => self.check_call(context, call),
+                Statement::Return(value, span) => {
+                    let subroutine = context.subroutine;
+                    let message = match (value, &subroutine.return_type) {
+                        (Some(_), Type::Void) => format!("`{}` is void and cannot return a value", context.name()),
+                        (None, return_type) if *return_type != Type::Void => {
+                            format!("`{}` must return a value of type `{}`", context.name(), return_type.name())
+                        }
+                        _ => String::new(),
+                    };
+                    if !message.is_empty() {
+                        self.diagnostics.push(Diagnostic::new(*span, message));
+                    }
+                    if let Some(value) = value {
+                        self.check_expression(context, value);
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_expression(&mut self, context: &Context, expression: &Expression) {
+        self.check_term(context, &expression.term);
+        for (_, term) in &expression.operations {
+            self.check_term(context, term);
+        }
+    }
+
+    fn check_term(&mut self, context: &Context, term: &Term) {
+        match term {
+            Term::KeywordConstant(KeywordConstant::This, span) if context.is_function() => {
+                let message = format!("`this` cannot be used in function `{}`", context.name());
+                self.diagnostics.push(Diagnostic::new(*span, message));
+            }
+            Term::Variable(name) => self.check_variable(context, name),
+            Term::ArrayElement(name, index) => {
+                self.check_variable(context, name);
+                self.check_expression(context, index);
+            }
+            Term::Parenthesized(expression) => self.check_expression(context, expression),
+            Term::Unary(_, term) => self.check_term(context, term),
+            Term::Call(call) => self.check_call(context, call),
+            _ => {}
+        }
+    }
+
+    // Functions have no object, so no fields
+    fn check_variable(&mut self, context: &Context, name: &Identifier) {
+        let is_field = self.symbols.get(&name.name).is_some_and(|symbol| symbol.kind == Kind::Field);
+        if is_field && context.is_function() {
+            let message = format!("field `{}` cannot be used in function `{}`", name.name, context.name());
+            self.error(name, message);
+        }
+    }
+
+    fn check_call(&mut self, context: &Context, call: &SubroutineCall) {
+        let class_name = self.class_name;
+        match &call.receiver {
+            // foo(): a method called on this
+            None => {
+                if let Some(target) = self.target(class_name, call) {
+                    if target.kind != SubroutineKind::Method {
+                        self.not_a_method(class_name, call, target);
+                    } else if context.is_function() {
+                        let message = format!(
+                            "method `{}` cannot be called without an object in function `{}`",
+                            call.name.name,
+                            context.name()
+                        );
+                        self.error(&call.name, message);
+                    }
+                }
+            }
+            Some(receiver) => match self.symbols.get(&receiver.name).cloned() {
+                // x.foo(): a method of the class of x
+                Some(symbol) => {
+                    self.check_variable(context, receiver);
+                    match symbol.type_name.as_str() {
+                        "int" | "char" | "boolean" => {
+                            let message = format!(
+                                "`{}` is of type `{}` and has no subroutine `{}`",
+                                receiver.name, symbol.type_name, call.name.name
+                            );
+                            self.error(&call.name, message);
+                        }
+                        class => {
+                            let target = self.target(class, call);
+                            if let Some(target) = target.filter(|target| target.kind != SubroutineKind::Method) {
+                                self.not_a_method(class, call, target);
+                            }
+                        }
+                    }
+                }
+                // Foo.bar(): a function or constructor of class Foo
+                None => {
+                    if self.program.complete && !self.program.has_class(&receiver.name) {
+                        let message = format!("unknown class or variable `{}`", receiver.name);
+                        self.error(receiver, message);
+                    }
+                    let target = self.target(&receiver.name, call);
+                    if target.is_some_and(|target| target.kind == SubroutineKind::Method) {
+                        let message = format!("method `{}` cannot be called without an object", call.name.name);
+                        self.error(&call.name, message);
+                    }
+                }
+            },
+        }
+
+        for argument in &call.arguments {
+            self.check_expression(context, argument);
+        }
+    }
+
+    // A function or constructor of `class` called on an object
+    fn not_a_method(&mut self, class: &str, call: &SubroutineCall, target: Signature) {
+        let name = &call.name.name;
+        let message = format!("{} `{}` must be called as `{}.{}`", target.kind.as_str(), name, class, name);
+        self.error(&call.name, message);
+    }
+
+    // Subroutine of `class` called by `call`, reports unknown subroutines and wrong argument counts.
+    // Classes outside of the program are not checked.
+    fn target(&mut self, class: &str, call: &SubroutineCall) -> Option<Signature> {
+        if !self.program.has_class(class) {
+            return None;
+        }
+        let name = &call.name;
+        let Some(target) = self.program.signature(class, &name.name) else {
+            let message = format!("unknown subroutine `{}.{}`", class, name.name);
+            self.error(name, message);
+            return None;
+        };
+
+        let expected = target.parameters;
+        if call.arguments.len() != expected {
+            let message = format!(
+                "`{}.{}` expects {} argument{} but got {}",
+                class,
+                name.name,
+                expected,
+                if expected == 1 { "" } else { "s" },
+                call.arguments.len()
+            );
+            self.error(name, message);
+        }
+        Some(target)
+    }
+}
+
+// The last statement returns, or every branch of the last `if` does
+fn ends_with_return(statements: &[Statement]) -> bool {
+    match statements.last() {
+        Some(Statement::Return(..)) => true,
+        Some(Statement::If { statements, else_statements: Some(else_statements), .. }) => {
+            ends_with_return(statements) && ends_with_return(else_statements)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::tokenizer::tokenize_source;
+
+    fn parse_class(source: &str) -> Class {
+        tokenize_source(source).and_then(parse).expect("the test class parses")
+    }
+
+    // `line:column: message` of the diagnostics of the class checked alone
+    fn diagnostics(source: &str) -> Vec<String> {
+        format(check(&parse_class(source)))
+    }
+
+    // Diagnostics of the first class, checked with the others and the OS
+    fn program_diagnostics(sources: &[&str]) -> Vec<String> {
+        let classes: Vec<Class> = sources.iter().map(|source| parse_class(source)).collect();
+        let program = Program::new(&classes);
+        format(check_program(&program, &classes[0]))
+    }
+
+    fn format(diagnostics: Vec<Diagnostic>) -> Vec<String> {
+        diagnostics
+            .into_iter()
+            .map(|d| format!("{}:{}: {}", d.span.line, d.span.column, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn duplicate_subroutine() {
+        let source = "class Main {
+            function void f() { return; }
+            method void f() { return; }
+        }";
+        assert_eq!(diagnostics(source), ["3:25: duplicate subroutine `f`"]);
+    }
+
+    #[test]
+    fn constructor_returns_its_class() {
+        let source = "class Main {
+            constructor int new() { return 0; }
+            constructor Main make() { return this; }
+        }";
+        assert_eq!(diagnostics(source), ["2:29: constructor `new` must return `Main`"]);
+    }
+
+    #[test]
+    fn missing_return() {
+        let source = "class Main {
+            function int f(int x) {
+                if (x) { return 1; }
+            }
+            function int g(int x) {
+                if (x) { return 1; } else { return 2; }
+            }
+            function void h() {
+                while (true) { return; }
+            }
+        }";
+        assert_eq!(
+            diagnostics(source),
+            ["2:26: `f` does not end with a return statement", "8:27: `h` does not end with a return statement"]
+        );
+    }
+
+    #[test]
+    fn return_value_matches_the_return_type() {
+        let source = "class Main {
+            function void f() { return 1; }
+            function int g() { return; }
+        }";
+        assert_eq!(
+            diagnostics(source),
+            ["2:33: `f` is void and cannot return a value", "3:32: `g` must return a value of type `int`"]
+        );
+    }
+
+    #[test]
+    fn this_in_function() {
+        let source = "class Main {
+            function Main f() { return this; }
+            method Main g() { return this; }
+        }";
+        assert_eq!(diagnostics(source), ["2:40: `this` cannot be used in function `f`"]);
+    }
+
+    #[test]
+    fn field_in_function() {
+        let source = "class Main {
+            field int x;
+            static int y;
+            function int f() { let y = x; return y; }
+            method int g() { let y = x; return x; }
+        }";
+        assert_eq!(diagnostics(source), ["4:40: field `x` cannot be used in function `f`"]);
+    }
+
+    #[test]
+    fn method_called_without_object() {
+        let source = "class Main {
+            method void m() { return; }
+            function void f() { do m(); return; }
+            function void g() { do Main.m(); return; }
+            method void h() { do m(); return; }
+        }";
+        assert_eq!(
+            diagnostics(source),
+            [
+                "3:36: method `m` cannot be called without an object in function `f`",
+                "4:41: method `m` cannot be called without an object",
+            ]
+        );
+    }
+
+    #[test]
+    fn function_called_as_method() {
+        let source = "class Main {
+            function void f() { return; }
+            method void m() {
+                var Main other;
+                var int n;
+                do f();
+                do other.f();
+                do n.f();
+                do Main.f();
+                return;
+            }
+        }";
+        assert_eq!(
+            diagnostics(source),
+            [
+                "6:20: function `f` must be called as `Main.f`",
+                "7:26: function `f` must be called as `Main.f`",
+                "8:22: `n` is of type `int` and has no subroutine `f`",
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_subroutine() {
+        let source = "class Main {
+            method void m() { do g(); do Main.h(); return; }
+        }";
+        assert_eq!(diagnostics(source), ["2:34: unknown subroutine `Main.g`", "2:47: unknown subroutine `Main.h`"]);
+    }
+
+    #[test]
+    fn argument_count() {
+        let source = "class Main {
+            function int f(int x) { return x; }
+            function int g() { return Main.f(1, 2) + Main.f() + Main.f(3); }
+        }";
+        assert_eq!(
+            diagnostics(source),
+            ["3:44: `Main.f` expects 1 argument but got 2", "3:59: `Main.f` expects 1 argument but got 0"]
+        );
+    }
+
+    #[test]
+    fn other_classes_are_not_checked_alone() {
+        let source = "class Main {
+            function void main() { var Foo foo; do Output.printInt(); do Foo.bar(1); return; }
+        }";
+        assert_eq!(diagnostics(source), Vec::<String>::new());
+    }
+
+    #[test]
+    fn os_calls_are_checked_against_the_os_signatures() {
+        let source = "class Main {
+            function void main() {
+                var String s;
+                do Output.printInt();
+                do Math.power(2, 3);
+                do Output.printString(s, 1);
+                do s.appendChar();
+                do String.length();
+                do Output.printInt(Math.max(1, 2));
+                return;
+            }
+        }";
+        assert_eq!(
+            program_diagnostics(&[source]),
+            [
+                "4:27: `Output.printInt` expects 1 argument but got 0",
+                "5:25: unknown subroutine `Math.power`",
+                "6:27: `Output.printString` expects 1 argument but got 2",
+                "7:22: `String.appendChar` expects 1 argument but got 0",
+                "8:27: method `length` cannot be called without an object",
+            ]
+        );
+    }
+
+    #[test]
+    fn calls_between_classes() {
+        let main = "class Main {
+            function void main() {
+                var Point p;
+                let p = Point.new(1);
+                do p.move(1, 2);
+                do p.distance(p, 1);
+                do Point.origin(1);
+                do p.origin();
+                do Point.move(3, 4);
+                return;
+            }
+        }";
+        let point = "class Point {
+            field int x, y;
+            constructor Point new(int ax, int ay) { let x = ax; let y = ay; return this; }
+            method void move(int dx, int dy) { let x = x + dx; let y = y + dy; return; }
+            method int distance(Point other) { return Math.abs(x - other.getX()); }
+            method int getX() { return x; }
+            function Point origin() { return Point.new(0, 0); }
+        }";
+        assert_eq!(
+            program_diagnostics(&[main, point]),
+            [
+                "4:31: `Point.new` expects 2 arguments but got 1",
+                "6:22: `Point.distance` expects 1 argument but got 2",
+                "7:26: `Point.origin` expects 0 arguments but got 1",
+                "8:22: function `origin` must be called as `Point.origin`",
+                "9:26: method `move` cannot be called without an object",
+            ]
+        );
+        assert_eq!(program_diagnostics(&[point, main]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn unknown_classes() {
+        let source = "class Main {
+            static Foo foo;
+            function Bar main(Baz baz) {
+                var Qux qux;
+                do Nope.f();
+                return null;
+            }
+        }";
+        assert_eq!(
+            program_diagnostics(&[source]),
+            [
+                "2:24: unknown class `Foo` for `foo`",
+                "3:26: unknown class `Bar` for `main`",
+                "3:35: unknown class `Baz` for `baz`",
+                "4:25: unknown class `Qux` for `qux`",
+                "5:20: unknown class or variable `Nope`",
+            ]
+        );
+    }
+
+    #[test]
+    fn program_classes_replace_the_os_classes() {
+        let main = "class Main {
+            function void main() { do Math.power(2, 3); do Math.sqrt(4); return; }
+        }";
+        let math = "class Math {
+            function int power(int x, int n) { return x; }
+        }";
+        assert_eq!(program_diagnostics(&[main, math]), ["2:65: unknown subroutine `Math.sqrt`"]);
+    }
+}
@@ -0,0 +1,87 @@
+use std::{error::Error, fmt, io};
+
+use crate::tokenizer::Span;
+
+// Position of an error in the source file (line and column start at 1),
+// with the source line to show where the error is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+    pub text: String,
+}
+
+// A problem found in a class, located by its span in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(span: Span, message: String) -> Self {
+        Self { span, message }
+    }
+
+    // `expected` was expected but `found` was found
+    pub fn unexpected(span: Span, expected: &str, found: &str) -> Self {
+        Self::new(span, format!("expected {} but found {}", expected, found))
+    }
+}
+
+#[derive(Debug)]
+pub enum JackError {
+    Io(String, io::Error), // file, cause
+    Compile(Location, String), // location, message
+}
+
+impl JackError {
+    // Locates the diagnostics of `file` whose content is `source`
+    pub fn from_diagnostics(file: &str, source: &str, diagnostics: Vec<Diagnostic>) -> Vec<JackError> {
+        let lines: Vec<&str> = source.lines().collect();
+        diagnostics
+            .into_iter()
+            .map(|diagnostic| {
+                let span = diagnostic.span;
+                let location = Location {
+                    file: file.to_string(),
+                    line: span.line,
+                    column: span.column,
+                    length: span.length,
+                    text: lines.get(span.line.wrapping_sub(1)).unwrap_or(&"").to_string(),
+                };
+                JackError::Compile(location, diagnostic.message)
+            })
+            .collect()
+    }
+}
+
+impl Error for JackError {}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+impl fmt::Display for JackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JackError::Io(file, err) => write!(f, "{}: {}", file, err),
+            // Main.jack:4:16: expected expression but found `;`
+            //    4 |     let x = 3 +;
+            //      |                ^
+            JackError::Compile(loc, message) => {
+                let number = loc.line.to_string();
+                let margin = " ".repeat(number.len());
+                // Tabs count as one column
+                let text = loc.text.replace('\t', " ");
+                let padding = " ".repeat(loc.column.saturating_sub(1));
+                let carets = "^".repeat(loc.length.max(1));
+                write!(f, "{}: {}\n {} | {}\n {} | {}{}", loc, message, number, text, margin, padding, carets)
+            }
+        }
+    }
+}
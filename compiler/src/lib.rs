@@ -1,7 +1,21 @@
-mod tokenizer;
+pub mod ast;
+pub mod tokenizer;
+mod parser;
+mod xml_writer;
+mod checker;
 mod compilation_engine;
+mod errors;
 mod analyzer;
 mod lexical;
+mod os;
+mod symbol_table;
+mod vm_writer;
 
-pub use analyzer::analyze;
-// pub use tokenizer::tokenize; // uncomment for tests
\ No newline at end of file
+pub use analyzer::{analyze, analyze_with_options, Options};
+pub use checker::{check, check_program, Program, Signature};
+pub use compilation_engine::CompilationEngine;
+pub use errors::{Diagnostic, JackError, Location};
+pub use os::{link_os, OS_CLASSES};
+pub use parser::{parse, parse_file};
+pub use tokenizer::{tokenize, tokenize_source, tokens_to_xml};
+pub use xml_writer::write_xml;
@@ -0,0 +1,101 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::ast::Class;
+use crate::compilation_engine::CompilationEngine;
+use crate::errors::JackError;
+use crate::parser::parse;
+use crate::tokenizer::tokenize_source;
+
+// The Jack OS: name and source of each class
+pub const OS_CLASSES: [(&str, &str); 8] = [
+    ("Array", include_str!("../os/Array.jack")),
+    ("Keyboard", include_str!("../os/Keyboard.jack")),
+    ("Math", include_str!("../os/Math.jack")),
+    ("Memory", include_str!("../os/Memory.jack")),
+    ("Output", include_str!("../os/Output.jack")),
+    ("Screen", include_str!("../os/Screen.jack")),
+    ("String", include_str!("../os/String.jack")),
+    ("Sys", include_str!("../os/Sys.jack")),
+];
+
+// Syntax trees of the OS classes
+pub fn os_classes() -> Vec<Class> {
+    OS_CLASSES.iter().map(|&(name, source)| parse_os_class(name, source)).collect()
+}
+
+fn parse_os_class(name: &str, source: &str) -> Class {
+    tokenize_source(source)
+        .and_then(parse)
+        .unwrap_or_else(|_| panic!("the OS class {} doesn't parse", name))
+}
+
+// VM code (class name, code) of the OS classes used by the `.vm` files of `dir`
+// and not defined there. Sys is always used: the bootstrap code calls `Sys.init`.
+pub fn link_os(dir: &str) -> Result<Vec<(String, String)>, Vec<JackError>> {
+    let io_error = |e| vec![JackError::Io(dir.to_string(), e)];
+    let mut defined = HashSet::new();
+    let mut used = vec!["Sys".to_string()];
+
+    for entry in fs::read_dir(dir).map_err(io_error)? {
+        let path = entry.map_err(io_error)?.path();
+        if path.extension().is_some_and(|ext| ext == "vm") {
+            let file = path.to_string_lossy();
+            let code = fs::read_to_string(&path).map_err(|e| vec![JackError::Io(file.to_string(), e)])?;
+            used.extend(called_classes(&code));
+            defined.extend(class_name(&path));
+        }
+    }
+
+    // The OS classes call each other
+    let mut linked = BTreeMap::new();
+    while let Some(name) = used.pop() {
+        if defined.contains(&name) || linked.contains_key(&name) {
+            continue;
+        }
+        let Some(&(name, source)) = OS_CLASSES.iter().find(|(os_name, _)| *os_name == name) else {
+            continue;
+        };
+        let code = CompilationEngine::compile(&parse_os_class(name, source))
+            .unwrap_or_else(|_| panic!("the OS class {} doesn't compile", name));
+        used.extend(called_classes(&code));
+        linked.insert(name.to_string(), code);
+    }
+    Ok(linked.into_iter().collect())
+}
+
+// Classes of the functions called by `call Foo.bar n` commands
+fn called_classes(code: &str) -> impl Iterator<Item = String> + '_ {
+    code.lines().filter_map(|line| {
+        let function = line.trim().strip_prefix("call ")?;
+        let (class, _) = function.split_once('.')?;
+        Some(class.to_string())
+    })
+}
+
+fn class_name(path: &Path) -> Option<String> {
+    path.file_stem().map(|stem| stem.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::{check_program, Program};
+
+    #[test]
+    fn every_os_class_parses_checks_and_compiles() {
+        let classes: Vec<Class> = OS_CLASSES.iter().map(|&(name, source)| parse_os_class(name, source)).collect();
+        // Sys.init calls Main.main
+        let main = parse_os_class("Main", "class Main { function void main() { return; } }");
+        let program = Program::new(classes.iter().chain([&main]));
+
+        for (class, (name, _)) in classes.iter().zip(OS_CLASSES) {
+            assert_eq!(class.name.name, name);
+            let diagnostics: Vec<String> = check_program(&program, class).into_iter().map(|d| d.message).collect();
+            assert_eq!(diagnostics, Vec::<String>::new(), "{}", name);
+            let code = CompilationEngine::compile(class).unwrap_or_else(|_| panic!("the OS class {} doesn't compile", name));
+            assert!(code.contains(&format!("function {}.", name)), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,510 @@
+use std::fs;
+
+use crate::ast::{
+    Class, ClassVarDec, Identifier, ClassVarKind, Expression, KeywordConstant, Op, Parameter, Statement, Subroutine,
+    SubroutineCall, SubroutineKind, Term, Type, UnaryOp, VarDec,
+};
+use crate::errors::{Diagnostic, JackError};
+use crate::tokenizer::{lex, Keyword, Span, SpannedToken, Token};
+
+// Syntax tree of a `.jack` file
+pub fn parse_file(path: &str) -> Result<Class, Vec<JackError>> {
+    let source = fs::read_to_string(path).map_err(|e| vec![JackError::Io(path.to_string(), e)])?;
+    parse_source(&source).map_err(|diagnostics| JackError::from_diagnostics(path, &source, diagnostics))
+}
+
+// Syntax tree of a Jack source. The parser goes on after the invalid tokens,
+// their errors and the syntax errors are reported together, in source order.
+pub(crate) fn parse_source(source: &str) -> Result<Class, Vec<Diagnostic>> {
+    let (tokens, mut diagnostics) = lex(source);
+    match parse(tokens) {
+        Ok(class) if diagnostics.is_empty() => Ok(class),
+        Ok(_) => Err(diagnostics),
+        Err(errors) => {
+            diagnostics.extend(errors);
+            diagnostics.sort_by_key(|diagnostic| (diagnostic.span.line, diagnostic.span.column));
+            Err(diagnostics)
+        }
+    }
+}
+
+// Builds the syntax tree of a class from its tokens.
+// A syntax error skips the broken statement or declaration, so every independent error is reported.
+pub fn parse(tokens: Vec<SpannedToken>) -> Result<Class, Vec<Diagnostic>> {
+    let mut parser = Parser { tokens, position: 0, diagnostics: Vec::new() };
+    let class = parser.parse_class();
+
+    match class {
+        Ok(class) if parser.diagnostics.is_empty() => Ok(class),
+        Ok(_) => Err(parser.diagnostics),
+        Err(diagnostic) => {
+            // A missing `}` at the end of the file is reported once
+            if parser.diagnostics.last() != Some(&diagnostic) {
+                parser.diagnostics.push(diagnostic);
+            }
+            Err(parser.diagnostics)
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<SpannedToken>,
+    position: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Parser {
+    // Get current token
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|t| &t.token)
+    }
+
+    // Get next token
+    fn peek_next_token(&self) -> Option<&Token> {
+        self.tokens.get(self.position + 1).map(|t| &t.token)
+    }
+
+    fn is_keyword(&self, keyword: Keyword) -> bool {
+        self.peek() == Some(&Token::Keyword(keyword))
+    }
+
+    fn is_symbol(&self, symbol: char) -> bool {
+        self.peek() == Some(&Token::Symbol(symbol))
+    }
+
+    // Consume the current token
+    fn advance(&mut self) -> Result<Token, Diagnostic> {
+        match self.peek().cloned() {
+            Some(token) => {
+                self.position += 1;
+                Ok(token)
+            }
+            None => Err(self.unexpected("a token")),
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: Keyword) -> Result<(), Diagnostic> {
+        if self.is_keyword(keyword) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("`{}`", keyword.as_str())))
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: char) -> Result<(), Diagnostic> {
+        if self.is_symbol(symbol) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("`{}`", symbol)))
+        }
+    }
+
+    fn expect_identifier(&mut self) -> Result<Identifier, Diagnostic> {
+        match self.tokens.get(self.position).cloned() {
+            Some(SpannedToken { token: Token::Identifier(name), span }) => {
+                self.position += 1;
+                Ok(Identifier { name, span })
+            }
+            _ => Err(self.unexpected("identifier")),
+        }
+    }
+
+    // `expected` is not the current token
+    fn unexpected(&self, expected: &str) -> Diagnostic {
+        match self.peek() {
+            Some(token) => Diagnostic::unexpected(self.span(), expected, &format!("`{}`", token)),
+            None => Diagnostic::unexpected(self.span(), expected, "end of file"),
+        }
+    }
+
+    // Span of the current token
+    fn span(&self) -> Span {
+        match self.tokens.get(self.position) {
+            Some(token) => token.span,
+            None => self.end_of_file(),
+        }
+    }
+
+    // Just after the last token
+    fn end_of_file(&self) -> Span {
+        match self.tokens.last() {
+            Some(token) => Span { column: token.span.column + token.span.length, length: 1, ..token.span },
+            None => Span { line: 1, column: 1, length: 1 },
+        }
+    }
+
+    // 'class' className '{' classVarDec* subroutineDec* '}'
+    fn parse_class(&mut self) -> Result<Class, Diagnostic> {
+        self.expect_keyword(Keyword::Class)?;
+        let name = self.expect_identifier()?;
+        self.expect_symbol('{')?;
+
+        let mut vars = Vec::new();
+        let mut subroutines = Vec::new();
+        loop {
+            let span = self.span();
+            let result = match self.peek() {
+                Some(Token::Keyword(Keyword::Static | Keyword::Field)) => {
+                    if !subroutines.is_empty() {
+                        let message = "class variables must be declared before subroutines".to_string();
+                        self.diagnostics.push(Diagnostic::new(span, message));
+                    }
+                    self.parse_class_var_dec().map(|dec| vars.push(dec))
+                }
+                Some(Token::Keyword(Keyword::Constructor | Keyword::Function | Keyword::Method)) => {
+                    self.parse_subroutine().map(|subroutine| subroutines.push(subroutine))
+                }
+                Some(Token::Symbol('}')) | None => break,
+                Some(_) => {
+                    let diagnostic = self.unexpected("subroutine declaration or `}`");
+                    self.position += 1;
+                    Err(diagnostic)
+                }
+            };
+            if let Err(diagnostic) = result {
+                self.diagnostics.push(diagnostic);
+                self.skip_to_declaration();
+            }
+        }
+
+        self.expect_symbol('}')?;
+        Ok(Class { name, vars, subroutines })
+    }
+
+    // Error recovery: skips the tokens of a broken declaration,
+    // up to the next class variable or subroutine declaration
+    fn skip_to_declaration(&mut self) {
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Keyword(
+                    Keyword::Static | Keyword::Field | Keyword::Constructor | Keyword::Function | Keyword::Method,
+                ) => return,
+                // The end of the class
+                Token::Symbol('}') if self.position + 1 == self.tokens.len() => return,
+                _ => self.position += 1,
+            }
+        }
+    }
+
+    // Error recovery: skips the tokens of a broken statement,
+    // up to a `;` (consumed), a `}` or the keyword of the next statement.
+    // The blocks of the broken statement are skipped as a whole.
+    fn skip_to_statement(&mut self) {
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Symbol('{') => {
+                    depth += 1;
+                    self.position += 1;
+                }
+                Token::Symbol('}') if depth > 0 => {
+                    depth -= 1;
+                    self.position += 1;
+                }
+                _ if depth > 0 => self.position += 1,
+                Token::Symbol(';') => {
+                    self.position += 1;
+                    return;
+                }
+                Token::Symbol('}')
+                | Token::Keyword(Keyword::Let | Keyword::If | Keyword::While | Keyword::Do | Keyword::Return) => return,
+                _ => self.position += 1,
+            }
+        }
+    }
+
+    // ('static' | 'field') type varName (',' varName)* ';'
+    fn parse_class_var_dec(&mut self) -> Result<ClassVarDec, Diagnostic> {
+        let kind = if self.advance()? == Token::Keyword(Keyword::Static) {
+            ClassVarKind::Static
+        } else {
+            ClassVarKind::Field
+        };
+        let (type_name, names) = self.parse_var_names()?;
+        Ok(ClassVarDec { kind, type_name, names })
+    }
+
+    // type varName (',' varName)* ';'
+    fn parse_var_names(&mut self) -> Result<(Type, Vec<Identifier>), Diagnostic> {
+        let type_name = self.parse_type()?;
+        let mut names = vec![self.expect_identifier()?];
+        while self.is_symbol(',') {
+            self.advance()?;
+            names.push(self.expect_identifier()?);
+        }
+        self.expect_symbol(';')?;
+        Ok((type_name, names))
+    }
+
+    // 'int' | 'char' | 'boolean' | 'void' | className
+    fn parse_type(&mut self) -> Result<Type, Diagnostic> {
+        let type_name = match self.peek() {
+            Some(Token::Identifier(name)) => Type::Class(name.clone()),
+            Some(Token::Keyword(Keyword::Int)) => Type::Int,
+            Some(Token::Keyword(Keyword::Char)) => Type::Char,
+            Some(Token::Keyword(Keyword::Boolean)) => Type::Boolean,
+            Some(Token::Keyword(Keyword::Void)) => Type::Void,
+            _ => return Err(self.unexpected("type")),
+        };
+        self.advance()?;
+        Ok(type_name)
+    }
+
+    // ('constructor' | 'function' | 'method') ('void' | type) subroutineName
+    // '(' parameterList ')' '{' varDec* statements '}'
+    fn parse_subroutine(&mut self) -> Result<Subroutine, Diagnostic> {
+        let kind = match self.advance()? {
+            Token::Keyword(Keyword::Constructor) => SubroutineKind::Constructor,
+            Token::Keyword(Keyword::Function) => SubroutineKind::Function,
+            _ => SubroutineKind::Method,
+        };
+        let return_type = self.parse_type()?;
+        let name = self.expect_identifier()?;
+
+        self.expect_symbol('(')?;
+        let parameters = self.parse_parameter_list()?;
+        self.expect_symbol(')')?;
+
+        self.expect_symbol('{')?;
+        let mut vars = Vec::new();
+        while self.is_keyword(Keyword::Var) {
+            self.advance()?;
+            let (type_name, names) = self.parse_var_names()?;
+            vars.push(VarDec { type_name, names });
+        }
+        let statements = self.parse_statements();
+        self.expect_symbol('}')?;
+
+        Ok(Subroutine { kind, return_type, name, parameters, vars, statements })
+    }
+
+    // ((type varName) (',' type varName)*)?
+    fn parse_parameter_list(&mut self) -> Result<Vec<Parameter>, Diagnostic> {
+        let mut parameters = Vec::new();
+        if self.is_symbol(')') {
+            return Ok(parameters);
+        }
+
+        loop {
+            let type_name = self.parse_type()?;
+            let name = self.expect_identifier()?;
+            parameters.push(Parameter { type_name, name });
+            if !self.is_symbol(',') {
+                break;
+            }
+            self.advance()?;
+        }
+        Ok(parameters)
+    }
+
+    // statement*
+    // A broken statement is reported and skipped
+    fn parse_statements(&mut self) -> Vec<Statement> {
+        let mut statements = Vec::new();
+        loop {
+            // Each statement consumes its keyword first, so recovering always moves forward
+            let result = match self.peek() {
+                Some(Token::Keyword(Keyword::Let)) => self.parse_let(),
+                Some(Token::Keyword(Keyword::If)) => self.parse_if(),
+                Some(Token::Keyword(Keyword::While)) => self.parse_while(),
+                Some(Token::Keyword(Keyword::Do)) => self.parse_do(),
+                Some(Token::Keyword(Keyword::Return)) => self.parse_return(),
+                _ => break, // Stop if there no longer statement
+            };
+            match result {
+                Ok(statement) => statements.push(statement),
+                Err(diagnostic) => {
+                    self.diagnostics.push(diagnostic);
+                    self.skip_to_statement();
+                }
+            }
+        }
+        statements
+    }
+
+    // 'let' varName ('[' expression ']')? '=' expression ';'
+    fn parse_let(&mut self) -> Result<Statement, Diagnostic> {
+        self.advance()?; // let
+        let name = self.expect_identifier()?;
+
+        let index = if self.is_symbol('[') {
+            self.advance()?;
+            let index = self.parse_expression()?;
+            self.expect_symbol(']')?;
+            Some(index)
+        } else {
+            None
+        };
+
+        self.expect_symbol('=')?;
+        let value = self.parse_expression()?;
+        self.expect_symbol(';')?;
+        Ok(Statement::Let { name, index, value })
+    }
+
+    // 'if' '(' expression ')' '{' statements '}' ('else' '{' statements '}')?
+    fn parse_if(&mut self) -> Result<Statement, Diagnostic> {
+        self.advance()?; // if
+        let condition = self.parse_condition()?;
+        let statements = self.parse_block()?;
+
+        let else_statements = if self.is_keyword(Keyword::Else) {
+            self.advance()?;
+            Some(self.parse_block()?)
+        } else {
+            None
+        };
+        Ok(Statement::If { condition, statements, else_statements })
+    }
+
+    // 'while' '(' expression ')' '{' statements '}'
+    fn parse_while(&mut self) -> Result<Statement, Diagnostic> {
+        self.advance()?; // while
+        let condition = self.parse_condition()?;
+        let statements = self.parse_block()?;
+        Ok(Statement::While { condition, statements })
+    }
+
+    // '(' expression ')'
+    fn parse_condition(&mut self) -> Result<Expression, Diagnostic> {
+        self.expect_symbol('(')?;
+        let condition = self.parse_expression()?;
+        self.expect_symbol(')')?;
+        Ok(condition)
+    }
+
+    // '{' statements '}'
+    fn parse_block(&mut self) -> Result<Vec<Statement>, Diagnostic> {
+        self.expect_symbol('{')?;
+        let statements = self.parse_statements();
+        self.expect_symbol('}')?;
+        Ok(statements)
+    }
+
+    // 'do' subroutineCall ';'
+    fn parse_do(&mut self) -> Result<Statement, Diagnostic> {
+        self.advance()?; // do
+        let name = self.expect_identifier()?;
+        let call = self.parse_subroutine_call(name)?;
+        self.expect_symbol(';')?;
+        Ok(Statement::Do(call))
+    }
+
+    // 'return' expression? ';'
+    fn parse_return(&mut self) -> Result<Statement, Diagnostic> {
+        let span = self.span();
+        self.advance()?; // return
+        let value = if self.is_symbol(';') { None } else { Some(self.parse_expression()?) };
+        self.expect_symbol(';')?;
+        Ok(Statement::Return(value, span))
+    }
+
+    // The rest of a subroutine call once its first identifier is read
+    // subroutineName '(' expressionList ')' | (className | varName) '.' subroutineName '(' expressionList ')'
+    fn parse_subroutine_call(&mut self, name: Identifier) -> Result<SubroutineCall, Diagnostic> {
+        let (receiver, name) = if self.is_symbol('.') {
+            self.advance()?;
+            (Some(name), self.expect_identifier()?)
+        } else {
+            (None, name)
+        };
+
+        self.expect_symbol('(')?;
+        let arguments = self.parse_expression_list()?;
+        self.expect_symbol(')')?;
+        Ok(SubroutineCall { receiver, name, arguments })
+    }
+
+    // term (op term)*
+    fn parse_expression(&mut self) -> Result<Expression, Diagnostic> {
+        let term = self.parse_term()?;
+        let mut operations = Vec::new();
+
+        while let Some(&Token::Symbol(symbol)) = self.peek() {
+            let op = match Op::from_symbol(symbol) {
+                Some(op) => op,
+                None => break,
+            };
+            self.advance()?;
+            operations.push((op, self.parse_term()?));
+        }
+        Ok(Expression { term, operations })
+    }
+
+    // integerConstant | stringConstant | keywordConstant | varName | varName '[' expression ']'
+    // | '(' expression ')' | (unaryOp term) | subroutineCall
+    fn parse_term(&mut self) -> Result<Term, Diagnostic> {
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => return Err(self.unexpected("term")),
+        };
+
+        let term = match token {
+            Token::IntConst(number) => {
+                self.advance()?;
+                Term::IntegerConstant(number)
+            }
+            Token::StringConst(value) => {
+                self.advance()?;
+                Term::StringConstant(value)
+            }
+            Token::Keyword(keyword) => {
+                let keyword = match keyword {
+                    Keyword::True => KeywordConstant::True,
+                    Keyword::False => KeywordConstant::False,
+                    Keyword::Null => KeywordConstant::Null,
+                    Keyword::This => KeywordConstant::This,
+                    _ => return Err(self.unexpected("expression")),
+                };
+                let span = self.span();
+                self.advance()?;
+                Term::KeywordConstant(keyword, span)
+            }
+            Token::Symbol('(') => {
+                self.advance()?;
+                let expression = self.parse_expression()?;
+                self.expect_symbol(')')?;
+                Term::Parenthesized(Box::new(expression))
+            }
+            Token::Symbol(symbol @ ('-' | '~')) => {
+                self.advance()?;
+                let op = if symbol == '-' { UnaryOp::Neg } else { UnaryOp::Not };
+                Term::Unary(op, Box::new(self.parse_term()?))
+            }
+            Token::Identifier(_) => {
+                let next = self.peek_next_token().cloned();
+                let name = self.expect_identifier()?;
+                match next {
+                    Some(Token::Symbol('(' | '.')) => Term::Call(self.parse_subroutine_call(name)?),
+                    // `sum[i]`
+                    Some(Token::Symbol('[')) => {
+                        self.advance()?;
+                        let index = self.parse_expression()?;
+                        self.expect_symbol(']')?;
+                        Term::ArrayElement(name, Box::new(index))
+                    }
+                    _ => Term::Variable(name),
+                }
+            }
+            _ => return Err(self.unexpected("expression")),
+        };
+        Ok(term)
+    }
+
+    // (expression (',' expression)*)?
+    fn parse_expression_list(&mut self) -> Result<Vec<Expression>, Diagnostic> {
+        let mut expressions = Vec::new();
+        if self.is_symbol(')') {
+            return Ok(expressions);
+        }
+
+        expressions.push(self.parse_expression()?);
+        while self.is_symbol(',') {
+            self.advance()?;
+            expressions.push(self.parse_expression()?);
+        }
+        Ok(expressions)
+    }
+}
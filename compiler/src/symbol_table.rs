@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use crate::ast::{Class, ClassVarKind, Identifier, Subroutine, SubroutineKind};
+use crate::errors::Diagnostic;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Static,
+    Field,
+    Arg,
+    Var,
+}
+
+impl Kind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Static => "static",
+            Kind::Field => "field",
+            Kind::Arg => "argument",
+            Kind::Var => "var",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub type_name: String,
+    pub kind: Kind,
+    pub index: u16,
+}
+
+// Class scope (static, field) and subroutine scope (argument, var)
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    class_scope: HashMap<String, Symbol>,
+    subroutine_scope: HashMap<String, Symbol>,
+    counts: HashMap<Kind, u16>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Empties the subroutine scope and resets the argument and var indexes
+    pub fn start_subroutine(&mut self) {
+        self.subroutine_scope.clear();
+        self.counts.remove(&Kind::Arg);
+        self.counts.remove(&Kind::Var);
+    }
+
+    // Adds a variable with the next index of its kind.
+    // Fails if the name is already defined in the same scope,
+    // a subroutine variable may hide a class variable.
+    pub fn define(&mut self, name: &str, type_name: &str, kind: Kind) -> Result<&Symbol, String> {
+        let scope = match kind {
+            Kind::Static | Kind::Field => &mut self.class_scope,
+            Kind::Arg | Kind::Var => &mut self.subroutine_scope,
+        };
+        if let Some(symbol) = scope.get(name) {
+            return Err(format!(
+                "duplicate identifier `{}`, already defined as {} {}",
+                name,
+                symbol.kind.as_str(),
+                symbol.type_name
+            ));
+        }
+
+        let index = self.counts.get(&kind).copied().unwrap_or(0);
+        self.counts.insert(kind, index + 1);
+
+        let symbol = Symbol {
+            type_name: type_name.to_string(),
+            kind,
+            index,
+        };
+        Ok(scope.entry(name.to_string()).or_insert(symbol))
+    }
+
+    // Defines the static and field variables of `class`, reports duplicates
+    pub fn define_class(&mut self, class: &Class) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for dec in &class.vars {
+            let kind = match dec.kind {
+                ClassVarKind::Static => Kind::Static,
+                ClassVarKind::Field => Kind::Field,
+            };
+            for name in &dec.names {
+                self.define_identifier(name, dec.type_name.name(), kind, &mut diagnostics);
+            }
+        }
+        diagnostics
+    }
+
+    // Starts the scope of `subroutine`: `this` for a method, then its parameters and locals.
+    // Reports duplicates.
+    pub fn define_subroutine(&mut self, class_name: &str, subroutine: &Subroutine) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        self.start_subroutine();
+        if subroutine.kind == SubroutineKind::Method {
+            self.define("this", class_name, Kind::Arg).ok();
+        }
+        for parameter in &subroutine.parameters {
+            self.define_identifier(&parameter.name, parameter.type_name.name(), Kind::Arg, &mut diagnostics);
+        }
+        for dec in &subroutine.vars {
+            for name in &dec.names {
+                self.define_identifier(name, dec.type_name.name(), Kind::Var, &mut diagnostics);
+            }
+        }
+        diagnostics
+    }
+
+    fn define_identifier(&mut self, name: &Identifier, type_name: &str, kind: Kind, diagnostics: &mut Vec<Diagnostic>) {
+        if let Err(message) = self.define(&name.name, type_name, kind) {
+            diagnostics.push(Diagnostic::new(name.span, message));
+        }
+    }
+
+    // Number of variables of the given kind defined in the current scope
+    pub fn var_count(&self, kind: Kind) -> u16 {
+        self.counts.get(&kind).copied().unwrap_or(0)
+    }
+
+    // Looks up the subroutine scope first, then the class scope
+    pub fn get(&self, name: &str) -> Option<&Symbol> {
+        self.subroutine_scope.get(name).or_else(|| self.class_scope.get(name))
+    }
+
+    // Same as `get` but reports undefined variables
+    pub fn lookup(&self, name: &str) -> Result<&Symbol, String> {
+        self.get(name).ok_or_else(|| format!("undefined variable `{}`", name))
+    }
+}
@@ -1,18 +1,18 @@
+use crate::errors::{Diagnostic, JackError};
 use crate::lexical::{KEYWORDS, SYMBOLS};
-use std::{
-    fs::File,
-    io::{self, BufRead},
-};
+use std::{fmt, fs};
 
+// A token with its value
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Keyword(Keyword),
-    Symbol,
-    Identifier,
-    IntConst,
-    StringConst,
+    Symbol(char),
+    Identifier(String),
+    IntConst(u16),
+    StringConst(String),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Keyword {
     Class,
     Method,
@@ -37,247 +37,265 @@ pub enum Keyword {
     This,
 }
 
-pub fn tokenize(path: &str) -> Result<Vec<String>, io::Error> {
-    let mut tokens: Vec<String> = Vec::new();
-    tokens.push("<tokens>".to_string());
-
-    let f = File::open(path)?;
-    let reader = io::BufReader::new(f);
-    let mut inside_comment_block = false;
+// Position of a token in its source file (line and column start at 1)
+// and its number of characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+}
 
-    for line in reader.lines() {
-        let line = line?;
-        let line = line.trim_start();
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
 
-        // Ignore comments like // xxxx
-        if line.starts_with("//") {
-            continue;
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Keyword(keyword) => write!(f, "{}", keyword.as_str()),
+            Token::Symbol(symbol) => write!(f, "{}", symbol),
+            Token::Identifier(name) => write!(f, "{}", name),
+            Token::IntConst(number) => write!(f, "{}", number),
+            Token::StringConst(value) => write!(f, "\"{}\"", value),
         }
+    }
+}
 
-        // Ignore comment like /** xxxx */
-        if line.starts_with("/**") {
-            inside_comment_block = true;
-        }
-        if inside_comment_block {
-            if line.ends_with("*/") {
-                inside_comment_block = false;
-            }
-            continue;
-        }
+// Tokens of a `.jack` file
+pub fn tokenize(path: &str) -> Result<Vec<SpannedToken>, Vec<JackError>> {
+    let source = fs::read_to_string(path).map_err(|e| vec![JackError::Io(path.to_string(), e)])?;
+    tokenize_source(&source).map_err(|diagnostics| JackError::from_diagnostics(path, &source, diagnostics))
+}
 
-        let words: Vec<String> = split_line_into_tokens(&line);
+// Tokens of a Jack source, reports every invalid token
+pub fn tokenize_source(source: &str) -> Result<Vec<SpannedToken>, Vec<Diagnostic>> {
+    let (tokens, diagnostics) = lex(source);
+    if diagnostics.is_empty() { Ok(tokens) } else { Err(diagnostics) }
+}
 
-        for word in words {
-            match token_type(&word) {
-                Some(Token::Keyword(keyword)) => {
-                    tokens.push(format!(
-                        "<keyword> {} </keyword>",
-                        keyword_to_string(&keyword)
-                    ));
-                }
-                Some(Token::Symbol) => {
-                    let escape_word = escape_special_chars(&word);
-                    tokens.push(format!("<symbol> {} </symbol>", escape_word));
-                }
-                Some(Token::Identifier) => {
-                    tokens.push(format!("<identifier> {} </identifier>", word));
-                }
-                Some(Token::IntConst) => {
-                    tokens.push(format!("<integerConstant> {} </integerConstant>", word));
-                }
-                Some(Token::StringConst) => {
-                    let clean_str = word.trim_matches('"');
-                    tokens.push(format!("<stringConstant> {} </stringConstant>", clean_str));
-                }
-                None => {}
-            }
-        }
-    }
+// Tokens and invalid tokens of a Jack source. An unterminated string or an integer
+// out of range still gives a token, so the parser goes on without an extra error.
+pub(crate) fn lex(source: &str) -> (Vec<SpannedToken>, Vec<Diagnostic>) {
+    let mut lexer = Lexer {
+        chars: source.chars().collect(),
+        position: 0,
+        line: 1,
+        column: 1,
+        tokens: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    lexer.run();
+    (lexer.tokens, lexer.diagnostics)
+}
 
-    tokens.push("</tokens>".to_string());
-    Ok(tokens)
+// Reads the source character by character, comments and strings may start anywhere
+struct Lexer {
+    chars: Vec<char>,
+    position: usize,
+    line: usize,
+    column: usize,
+    tokens: Vec<SpannedToken>,
+    diagnostics: Vec<Diagnostic>,
 }
 
-fn token_type(token: &str) -> Option<Token> {
-    if KEYWORDS.contains(&token) {
-        if let Some(key) = keyword_type(token) {
-            return Some(Token::Keyword(key));
-        }
-    } else if token.chars().all(|c| SYMBOLS.contains(&c)) {
-        return Some(Token::Symbol);
-    } else if is_valid_identifier(token) {
-        return Some(Token::Identifier);
-    } else if is_valid_integer(token) {
-        return Some(Token::IntConst);
-    } else if token.starts_with('"') && token.ends_with('"') {
-        return Some(Token::StringConst);
+impl Lexer {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
     }
 
-    None
-}
+    fn peek_next(&self) -> Option<char> {
+        self.chars.get(self.position + 1).copied()
+    }
 
-fn keyword_type(token: &str) -> Option<Keyword> {
-    let keywords = [
-        ("class", Keyword::Class),
-        ("method", Keyword::Method),
-        ("function", Keyword::Function),
-        ("constructor", Keyword::Constructor),
-        ("int", Keyword::Int),
-        ("boolean", Keyword::Boolean),
-        ("char", Keyword::Char),
-        ("void", Keyword::Void),
-        ("var", Keyword::Var),
-        ("static", Keyword::Static),
-        ("field", Keyword::Field),
-        ("let", Keyword::Let),
-        ("do", Keyword::Do),
-        ("if", Keyword::If),
-        ("else", Keyword::Else),
-        ("while", Keyword::While),
-        ("return", Keyword::Return),
-        ("true", Keyword::True),
-        ("false", Keyword::False),
-        ("null", Keyword::Null),
-        ("this", Keyword::This),
-    ];
+    // Consume the current character
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
 
-    for &(pat, keyword) in keywords.iter() {
-        if token == pat {
-            return Some(keyword);
+    // Consume characters while `accept` holds
+    fn bump_while(&mut self, accept: impl Fn(char) -> bool) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.peek().filter(|&c| accept(c)) {
+            word.push(c);
+            self.bump();
         }
+        word
     }
 
-    None
-}
+    fn run(&mut self) {
+        while let Some(c) = self.peek() {
+            let start = Span { line: self.line, column: self.column, length: 1 };
 
-fn keyword_to_string(keyword: &Keyword) -> &str {
-    match keyword {
-        Keyword::Class => "class",
-        Keyword::Method => "method",
-        Keyword::Function => "function",
-        Keyword::Constructor => "constructor",
-        Keyword::Int => "int",
-        Keyword::Boolean => "boolean",
-        Keyword::Char => "char",
-        Keyword::Void => "void",
-        Keyword::Var => "var",
-        Keyword::Static => "static",
-        Keyword::Field => "field",
-        Keyword::Let => "let",
-        Keyword::Do => "do",
-        Keyword::If => "if",
-        Keyword::Else => "else",
-        Keyword::While => "while",
-        Keyword::Return => "return",
-        Keyword::True => "true",
-        Keyword::False => "false",
-        Keyword::Null => "null",
-        Keyword::This => "this",
+            match c {
+                _ if c.is_whitespace() => {
+                    self.bump();
+                }
+                // Comment like // xxxx
+                '/' if self.peek_next() == Some('/') => {
+                    self.bump_while(|c| c != '\n');
+                }
+                // Comment like /* xxxx */ or /** xxxx */, possibly on several lines
+                '/' if self.peek_next() == Some('*') => {
+                    self.bump();
+                    self.bump();
+                    loop {
+                        match self.bump() {
+                            Some('*') if self.peek() == Some('/') => {
+                                self.bump();
+                                break;
+                            }
+                            Some(_) => {}
+                            None => {
+                                let span = Span { length: 2, ..start };
+                                self.error(span, "unterminated comment, `*/` is missing".to_string());
+                                break;
+                            }
+                        }
+                    }
+                }
+                // String constants end on the same line
+                '"' => {
+                    self.bump();
+                    let value = self.bump_while(|c| c != '"' && c != '\n');
+                    let span = Span { length: value.chars().count() + 2, ..start };
+                    if self.peek() == Some('"') {
+                        self.bump();
+                        self.push(Token::StringConst(value), span);
+                    } else {
+                        let span = Span { length: span.length - 1, ..span };
+                        self.error(span, "unterminated string constant, `\"` is missing".to_string());
+                        self.push(Token::StringConst(value), span);
+                    }
+                }
+                _ if c.is_ascii_digit() => {
+                    let word = self.bump_while(|c| c.is_ascii_digit());
+                    let span = Span { length: word.len(), ..start };
+                    match word.parse::<u16>() {
+                        Ok(number) if number <= 32767 => self.push(Token::IntConst(number), span),
+                        _ => {
+                            self.error(span, format!("integer constant `{}` is out of range (0..32767)", word));
+                            self.push(Token::IntConst(0), span);
+                        }
+                    }
+                }
+                _ if c.is_ascii_alphabetic() || c == '_' => {
+                    let word = self.bump_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                    let span = Span { length: word.len(), ..start };
+                    let token = match Keyword::from_str(&word) {
+                        Some(keyword) if KEYWORDS.contains(&word.as_str()) => Token::Keyword(keyword),
+                        _ => Token::Identifier(word),
+                    };
+                    self.push(token, span);
+                }
+                _ if SYMBOLS.contains(&c) => {
+                    self.bump();
+                    self.push(Token::Symbol(c), start);
+                }
+                _ => {
+                    self.bump();
+                    self.error(start, format!("illegal character `{}`", c));
+                }
+            }
+        }
     }
-}
 
-fn is_valid_identifier(token: &str) -> bool {
-    if token.is_empty() {
-        return false;
+    fn push(&mut self, token: Token, span: Span) {
+        self.tokens.push(SpannedToken { token, span });
     }
-    let first_char = token.chars().next().unwrap();
-    if !first_char.is_alphabetic() && first_char != '_' {
-        return false;
-    }
-    for ch in token.chars().skip(1) {
-        if !ch.is_alphanumeric() && ch != '_' {
-            return false;
-        }
+
+    fn error(&mut self, span: Span, message: String) {
+        self.diagnostics.push(Diagnostic::new(span, message));
     }
-    true
 }
 
-fn is_valid_integer(token: &str) -> bool {
-    match token.parse::<u32>() {
-        Ok(number) => number <= 32767,
-        Err(_) => false,
+// Renders tokens in the nand2tetris `<tokens>` XML format
+pub fn tokens_to_xml(tokens: &[SpannedToken]) -> String {
+    let mut xml = String::from("<tokens>\n");
+    for token in tokens {
+        let (tag, value) = match &token.token {
+            Token::Keyword(keyword) => ("keyword", keyword.as_str().to_string()),
+            Token::Symbol(symbol) => ("symbol", symbol.to_string()),
+            Token::Identifier(name) => ("identifier", name.clone()),
+            Token::IntConst(number) => ("integerConstant", number.to_string()),
+            Token::StringConst(value) => ("stringConstant", value.clone()),
+        };
+        xml.push_str(&format!("<{}> {} </{}>\n", tag, escape_special_chars(&value), tag));
     }
+    xml.push_str("</tokens>\n");
+    xml
 }
 
+impl Keyword {
+    pub fn from_str(token: &str) -> Option<Keyword> {
+        let keywords = [
+            ("class", Keyword::Class),
+            ("method", Keyword::Method),
+            ("function", Keyword::Function),
+            ("constructor", Keyword::Constructor),
+            ("int", Keyword::Int),
+            ("boolean", Keyword::Boolean),
+            ("char", Keyword::Char),
+            ("void", Keyword::Void),
+            ("var", Keyword::Var),
+            ("static", Keyword::Static),
+            ("field", Keyword::Field),
+            ("let", Keyword::Let),
+            ("do", Keyword::Do),
+            ("if", Keyword::If),
+            ("else", Keyword::Else),
+            ("while", Keyword::While),
+            ("return", Keyword::Return),
+            ("true", Keyword::True),
+            ("false", Keyword::False),
+            ("null", Keyword::Null),
+            ("this", Keyword::This),
+        ];
 
-fn split_line_into_tokens(line: &str) -> Vec<String> {
-  let mut result = Vec::new();
-  let mut current_token = String::new();
-  let mut inside_string = false;
-  let mut chars = line.chars().peekable();
-
-  while let Some(ch) = chars.next() {
-      if ch == '/' && chars.peek() == Some(&'/') {
-          break;
-      }
-
-      if ch == '/' && chars.peek() == Some(&'*') {
-          while let Some(c) = chars.next() {
-              if c == '*' && chars.peek() == Some(&'/') {
-                  chars.next();
-                  break;
-              }
-          }
-          continue;
-      }
-
-      if inside_string {
-          current_token.push(ch);
-          if ch == '"' {
-              result.push(current_token.clone());
-              current_token.clear();
-              inside_string = false;
-          }
-          continue;
-      }
-
-      match ch {
-          '"' => {
-              if !current_token.is_empty() {
-                  result.push(current_token.clone());
-                  current_token.clear();
-              }
-              inside_string = true;
-              current_token.push(ch);
-          }
-          '(' | ')' | '{' | '}' | ',' | '=' | '.' | ';' | '[' | ']' => {
-              if !current_token.is_empty() {
-                  result.push(current_token.clone());
-                  current_token.clear();
-              }
-              result.push(ch.to_string());
-          }
-          '-' => {
-              if !current_token.is_empty() {
-                  result.push(current_token.clone());
-                  current_token.clear();
-              }
-              result.push(ch.to_string());
-          }
-          _ if ch.is_alphanumeric() || (ch == '.' && !current_token.is_empty()) => {
-              current_token.push(ch);
-          }
-          ' ' => {
-              if !current_token.is_empty() {
-                  result.push(current_token.clone());
-                  current_token.clear();
-              }
-          }
-          _ => {
-              if !current_token.is_empty() {
-                  result.push(current_token.clone());
-                  current_token.clear();
-              }
-              result.push(ch.to_string());
-          }
-      }
-  }
+        for &(pat, keyword) in keywords.iter() {
+            if token == pat {
+                return Some(keyword);
+            }
+        }
 
-  if !current_token.is_empty() {
-      result.push(current_token);
-  }
+        None
+    }
 
-  result
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Keyword::Class => "class",
+            Keyword::Method => "method",
+            Keyword::Function => "function",
+            Keyword::Constructor => "constructor",
+            Keyword::Int => "int",
+            Keyword::Boolean => "boolean",
+            Keyword::Char => "char",
+            Keyword::Void => "void",
+            Keyword::Var => "var",
+            Keyword::Static => "static",
+            Keyword::Field => "field",
+            Keyword::Let => "let",
+            Keyword::Do => "do",
+            Keyword::If => "if",
+            Keyword::Else => "else",
+            Keyword::While => "while",
+            Keyword::Return => "return",
+            Keyword::True => "true",
+            Keyword::False => "false",
+            Keyword::Null => "null",
+            Keyword::This => "this",
+        }
+    }
 }
 
 // Replace &, <, >, \ by &amp;, &lt;, &gt;, &quot;
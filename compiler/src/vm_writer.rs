@@ -0,0 +1,98 @@
+use crate::symbol_table::Kind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment {
+    Constant,
+    Argument,
+    Local,
+    Static,
+    This,
+    That,
+    Pointer,
+    Temp,
+}
+
+impl Segment {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Segment::Constant => "constant",
+            Segment::Argument => "argument",
+            Segment::Local => "local",
+            Segment::Static => "static",
+            Segment::This => "this",
+            Segment::That => "that",
+            Segment::Pointer => "pointer",
+            Segment::Temp => "temp",
+        }
+    }
+}
+
+impl From<Kind> for Segment {
+    fn from(kind: Kind) -> Self {
+        match kind {
+            Kind::Static => Segment::Static,
+            Kind::Field => Segment::This,
+            Kind::Arg => Segment::Argument,
+            Kind::Var => Segment::Local,
+        }
+    }
+}
+
+// Collects the VM commands of a class
+#[derive(Debug, Default)]
+pub struct VMWriter {
+    code: String,
+}
+
+impl VMWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write_push(&mut self, segment: Segment, index: u16) {
+        self.write(&format!("push {} {}", segment.as_str(), index));
+    }
+
+    pub fn write_pop(&mut self, segment: Segment, index: u16) {
+        self.write(&format!("pop {} {}", segment.as_str(), index));
+    }
+
+    // add, sub, neg, eq, gt, lt, and, or, not
+    pub fn write_arithmetic(&mut self, command: &str) {
+        self.write(command);
+    }
+
+    pub fn write_label(&mut self, label: &str) {
+        self.write(&format!("label {}", label));
+    }
+
+    pub fn write_goto(&mut self, label: &str) {
+        self.write(&format!("goto {}", label));
+    }
+
+    pub fn write_if(&mut self, label: &str) {
+        self.write(&format!("if-goto {}", label));
+    }
+
+    pub fn write_call(&mut self, name: &str, nargs: u16) {
+        self.write(&format!("call {} {}", name, nargs));
+    }
+
+    pub fn write_function(&mut self, name: &str, nvars: u16) {
+        self.write(&format!("function {} {}", name, nvars));
+    }
+
+    pub fn write_return(&mut self) {
+        self.write("return");
+    }
+
+    // VM code written so far
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    fn write(&mut self, command: &str) {
+        self.code.push_str(command);
+        self.code.push('\n');
+    }
+}
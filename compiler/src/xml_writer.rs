@@ -0,0 +1,302 @@
+use crate::ast::{Class, ClassVarDec, Expression, Identifier, Statement, Subroutine, SubroutineCall, Term, Type};
+use crate::symbol_table::SymbolTable;
+
+// What an identifier names, for annotated XML
+#[derive(Debug, Clone, Copy)]
+enum Category {
+    Variable,
+    Class,
+    Subroutine,
+}
+
+// Parse tree of `class` in the nand2tetris XML format
+// With `annotate`, identifiers give their category, index and usage.
+// Undefined variables are written without annotations.
+pub fn write_xml(class: &Class, annotate: bool) -> String {
+    let mut writer = XmlWriter {
+        xml: String::new(),
+        symbols: SymbolTable::new(),
+        annotate,
+    };
+    writer.write_class(class);
+    writer.xml
+}
+
+struct XmlWriter {
+    xml: String,
+    symbols: SymbolTable,
+    annotate: bool,
+}
+
+impl XmlWriter {
+    fn write(&mut self, content: &str) {
+        self.xml.push_str(content);
+        self.xml.push('\n');
+    }
+
+    // <tag> value </tag>
+    fn write_token(&mut self, tag: &str, value: &str) {
+        self.write(&format!("<{}> {} </{}>", tag, escape_special_chars(value), tag));
+    }
+
+    fn write_keyword(&mut self, keyword: &str) {
+        self.write_token("keyword", keyword);
+    }
+
+    fn write_symbol(&mut self, symbol: &str) {
+        self.write_token("symbol", symbol);
+    }
+
+    // With annotations: <identifier category="var" index="0" usage="used"> x </identifier>
+    fn write_identifier(&mut self, name: &str, category: Category, usage: &str) {
+        let attributes = match category {
+            _ if !self.annotate => None,
+            Category::Class => Some("category=\"class\"".to_string()),
+            Category::Subroutine => Some("category=\"subroutine\"".to_string()),
+            Category::Variable => self
+                .symbols
+                .get(name)
+                .map(|symbol| format!("category=\"{}\" index=\"{}\"", symbol.kind.as_str(), symbol.index)),
+        };
+        let Some(attributes) = attributes else {
+            self.write_token("identifier", name);
+            return;
+        };
+        self.write(&format!(
+            "<identifier {} usage=\"{}\"> {} </identifier>",
+            attributes, usage, name
+        ));
+    }
+
+    fn write_type(&mut self, type_name: &Type) {
+        match type_name {
+            Type::Class(name) => self.write_identifier(name, Category::Class, "used"),
+            _ => {
+                self.write_keyword(type_name.name());
+            }
+        }
+    }
+
+    // name (',' name)*
+    fn write_variable_names(&mut self, names: &[Identifier]) {
+        for (i, name) in names.iter().enumerate() {
+            if i > 0 {
+                self.write_symbol(",");
+            }
+            self.write_identifier(&name.name, Category::Variable, "defined");
+        }
+    }
+
+    fn write_class(&mut self, class: &Class) {
+        self.symbols.define_class(class);
+
+        self.write("<class>");
+        self.write_keyword("class");
+        self.write_identifier(&class.name.name, Category::Class, "defined");
+        self.write_symbol("{");
+        for dec in &class.vars {
+            self.write_class_var_dec(dec);
+        }
+        for subroutine in &class.subroutines {
+            self.write_subroutine(&class.name.name, subroutine);
+        }
+        self.write_symbol("}");
+        self.write("</class>");
+    }
+
+    fn write_class_var_dec(&mut self, dec: &ClassVarDec) {
+        self.write("<classVarDec>");
+        self.write_keyword(dec.kind.as_str());
+        self.write_type(&dec.type_name);
+        self.write_variable_names(&dec.names);
+        self.write_symbol(";");
+        self.write("</classVarDec>");
+    }
+
+    fn write_subroutine(&mut self, class_name: &str, subroutine: &Subroutine) {
+        self.symbols.define_subroutine(class_name, subroutine);
+
+        self.write("<subroutineDec>");
+        self.write_keyword(subroutine.kind.as_str());
+        self.write_type(&subroutine.return_type);
+        self.write_identifier(&subroutine.name.name, Category::Subroutine, "defined");
+
+        self.write_symbol("(");
+        self.write("<parameterList>");
+        for (i, parameter) in subroutine.parameters.iter().enumerate() {
+            if i > 0 {
+                self.write_symbol(",");
+            }
+            self.write_type(&parameter.type_name);
+            self.write_identifier(&parameter.name.name, Category::Variable, "defined");
+        }
+        self.write("</parameterList>");
+        self.write_symbol(")");
+
+        self.write("<subroutineBody>");
+        self.write_symbol("{");
+        for dec in &subroutine.vars {
+            self.write("<varDec>");
+            self.write_keyword("var");
+            self.write_type(&dec.type_name);
+            self.write_variable_names(&dec.names);
+            self.write_symbol(";");
+            self.write("</varDec>");
+        }
+        self.write_statements(&subroutine.statements);
+        self.write_symbol("}");
+        self.write("</subroutineBody>");
+
+        self.write("</subroutineDec>");
+    }
+
+    fn write_statements(&mut self, statements: &[Statement]) {
+        self.write("<statements>");
+        for statement in statements {
+            self.write_statement(statement);
+        }
+        self.write("</statements>");
+    }
+
+    fn write_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Let { name, index, value } => {
+                self.write("<letStatement>");
+                self.write_keyword("let");
+                self.write_identifier(&name.name, Category::Variable, "used");
+                if let Some(index) = index {
+                    self.write_symbol("[");
+                    self.write_expression(index);
+                    self.write_symbol("]");
+                }
+                self.write_symbol("=");
+                self.write_expression(value);
+                self.write_symbol(";");
+                self.write("</letStatement>");
+            }
+            Statement::If {
+                condition,
+                statements,
+                else_statements,
+            } => {
+                self.write("<ifStatement>");
+                self.write_keyword("if");
+                self.write_condition(condition);
+                self.write_block(statements);
+                if let Some(else_statements) = else_statements {
+                    self.write_keyword("else");
+                    self.write_block(else_statements);
+                }
+                self.write("</ifStatement>");
+            }
+            Statement::While { condition, statements } => {
+                self.write("<whileStatement>");
+                self.write_keyword("while");
+                self.write_condition(condition);
+                self.write_block(statements);
+                self.write("</whileStatement>");
+            }
+            Statement::Do(call) => {
+                self.write("<doStatement>");
+                self.write_keyword("do");
+                self.write_subroutine_call(call);
+                self.write_symbol(";");
+                self.write("</doStatement>");
+            }
+            Statement::Return(value, _) => {
+                self.write("<returnStatement>");
+                self.write_keyword("return");
+                if let Some(value) = value {
+                    self.write_expression(value);
+                }
+                self.write_symbol(";");
+                self.write("</returnStatement>");
+            }
+        }
+    }
+
+    // '(' expression ')'
+    fn write_condition(&mut self, condition: &Expression) {
+        self.write_symbol("(");
+        self.write_expression(condition);
+        self.write_symbol(")");
+    }
+
+    // '{' statements '}'
+    fn write_block(&mut self, statements: &[Statement]) {
+        self.write_symbol("{");
+        self.write_statements(statements);
+        self.write_symbol("}");
+    }
+
+    // `foo(`: subroutine, `x.foo(`: variable if x is defined, class otherwise
+    fn write_subroutine_call(&mut self, call: &SubroutineCall) {
+        if let Some(receiver) = &call.receiver {
+            let category = if self.symbols.get(&receiver.name).is_some() {
+                Category::Variable
+            } else {
+                Category::Class
+            };
+            self.write_identifier(&receiver.name, category, "used");
+            self.write_symbol(".");
+        }
+        self.write_identifier(&call.name.name, Category::Subroutine, "used");
+
+        self.write_symbol("(");
+        self.write("<expressionList>");
+        for (i, argument) in call.arguments.iter().enumerate() {
+            if i > 0 {
+                self.write_symbol(",");
+            }
+            self.write_expression(argument);
+        }
+        self.write("</expressionList>");
+        self.write_symbol(")");
+    }
+
+    fn write_expression(&mut self, expression: &Expression) {
+        self.write("<expression>");
+        self.write_term(&expression.term);
+        for (op, term) in &expression.operations {
+            self.write_symbol(op.symbol());
+            self.write_term(term);
+        }
+        self.write("</expression>");
+    }
+
+    fn write_term(&mut self, term: &Term) {
+        self.write("<term>");
+        match term {
+            Term::IntegerConstant(number) => self.write_token("integerConstant", &number.to_string()),
+            Term::StringConstant(value) => self.write_token("stringConstant", value),
+            Term::KeywordConstant(keyword, _) => self.write_keyword(keyword.as_str()),
+            Term::Variable(name) => self.write_identifier(&name.name, Category::Variable, "used"),
+            Term::ArrayElement(name, index) => {
+                self.write_identifier(&name.name, Category::Variable, "used");
+                self.write_symbol("[");
+                self.write_expression(index);
+                self.write_symbol("]");
+            }
+            Term::Parenthesized(expression) => {
+                self.write_symbol("(");
+                self.write_expression(expression);
+                self.write_symbol(")");
+            }
+            Term::Unary(op, term) => {
+                self.write_symbol(op.symbol());
+                self.write_term(term);
+            }
+            Term::Call(call) => self.write_subroutine_call(call),
+        }
+        self.write("</term>");
+    }
+}
+
+// Replace &, <, >, " by &amp;, &lt;, &gt;, &quot;
+fn escape_special_chars(input: &str) -> String {
+    input
+        .replace("&", "&amp;")
+        .replace("<", "&lt;")
+        .replace(">", "&gt;")
+        .replace("\"", "&quot;")
+}
@@ -0,0 +1,258 @@
+use crate::errors::EmulatorError;
+use hack_assembler::assemble_file;
+use std::fs;
+use std::path::Path;
+
+pub const ROM_SIZE: usize = 32768;
+pub const RAM_SIZE: usize = 32768;
+pub const SCREEN: usize = 16384;
+pub const KBD: usize = 24576;
+
+// Most words a loop can write and still be seen as a halt
+const MAX_LOOP_WRITES: usize = 64;
+
+// Hack CPU with its ROM and RAM
+#[derive(Debug, Clone)]
+pub struct Cpu {
+    rom: Vec<u16>,
+    ram: Vec<u16>,
+    a: u16,
+    d: u16,
+    pc: u16,
+    cycles: u64,
+    halt: HaltCheck,
+}
+
+// Detects the endless loop that ends a program, whatever its instructions: a backward jump
+// taken twice with the same A, D and RAM, without reading the keyboard in between.
+// From there the CPU can only repeat the same instructions forever.
+#[derive(Debug, Clone, Default)]
+struct HaltCheck {
+    state: Option<(u16, u16, u16)>, // target, A and D of the last backward jump
+    written: Vec<(usize, u16)>,     // words written since then, with their value at the jump
+    halted: bool,
+}
+
+impl HaltCheck {
+    // `address` holding `value` is about to be written
+    fn write(&mut self, address: usize, value: u16) {
+        if self.state.is_none() || self.written.iter().any(|&(written, _)| written == address) {
+            return;
+        }
+        if self.written.len() == MAX_LOOP_WRITES {
+            self.state = None;
+        } else {
+            self.written.push((address, value));
+        }
+    }
+
+    fn backward_jump(&mut self, state: (u16, u16, u16), ram: &[u16]) {
+        if self.state == Some(state) && self.written.iter().all(|&(address, value)| ram[address] == value) {
+            self.halted = true;
+        } else {
+            self.state = Some(state);
+            self.written.clear();
+        }
+    }
+
+    // The state changed from outside the program, or the program read the keyboard
+    fn reset(&mut self) {
+        self.state = None;
+        self.written.clear();
+        self.halted = false;
+    }
+}
+
+impl Cpu {
+    pub fn new(rom: Vec<u16>) -> Result<Self, EmulatorError> {
+        if rom.len() > ROM_SIZE {
+            return Err(EmulatorError::RomTooLarge(rom.len()));
+        }
+
+        Ok(Self {
+            rom,
+            ram: vec![0; RAM_SIZE],
+            a: 0,
+            d: 0,
+            pc: 0,
+            cycles: 0,
+            halt: HaltCheck::default(),
+        })
+    }
+
+    // Loads a `.hack` binary file or assembles a `.asm` file
+    pub fn load(path: &str) -> Result<Self, EmulatorError> {
+        let rom = if Path::new(path).extension().is_some_and(|ext| ext == "asm") {
+            assemble_file(path).map_err(EmulatorError::Assembler)?
+        } else {
+            let source = fs::read_to_string(path).map_err(|e| EmulatorError::Io(path.to_string(), e))?;
+            parse_hack(&source, path)?
+        };
+
+        Self::new(rom)
+    }
+
+    // Executes one instruction
+    pub fn step(&mut self) {
+        let instruction = match self.rom.get(self.pc as usize) {
+            Some(&instruction) => instruction,
+            None => return, // out of the program: nothing left to run
+        };
+        self.cycles += 1;
+
+        // A instruction: @value
+        if instruction & 0x8000 == 0 {
+            self.a = instruction;
+            self.pc += 1;
+            return;
+        }
+
+        // C instruction: 111a cccc ccdd djjj
+        let address = (self.a as usize) % RAM_SIZE;
+        let y = if instruction & 0x1000 != 0 {
+            if address == KBD {
+                self.halt.reset();
+            }
+            self.ram[address]
+        } else {
+            self.a
+        };
+        let out = alu(self.d, y, (instruction >> 6) & 0x3F);
+
+        if instruction & 0x0008 != 0 {
+            self.halt.write(address, self.ram[address]);
+            self.ram[address] = out;
+        }
+        let jump_address = self.a;
+        if instruction & 0x0020 != 0 {
+            self.a = out;
+        }
+        if instruction & 0x0010 != 0 {
+            self.d = out;
+        }
+
+        let out = out as i16;
+        let jump = (instruction & 0x4 != 0 && out < 0)
+            || (instruction & 0x2 != 0 && out == 0)
+            || (instruction & 0x1 != 0 && out > 0);
+        if jump {
+            if jump_address <= self.pc {
+                self.halt.backward_jump((jump_address, self.a, self.d), &self.ram);
+            }
+            self.pc = jump_address;
+        } else {
+            self.pc += 1;
+        }
+    }
+
+    // Runs `cycles` instructions, stops early if the program halts.
+    // Returns the number of instructions executed.
+    pub fn run(&mut self, cycles: u64) -> u64 {
+        let start = self.cycles;
+        for _ in 0..cycles {
+            if self.is_halted() {
+                break;
+            }
+            self.step();
+        }
+        self.cycles - start
+    }
+
+    // Runs until the program halts, gives up after `max_cycles` instructions
+    pub fn run_until_halt(&mut self, max_cycles: u64) -> Result<u64, EmulatorError> {
+        let executed = self.run(max_cycles);
+        if self.is_halted() {
+            Ok(executed)
+        } else {
+            Err(EmulatorError::Timeout(max_cycles))
+        }
+    }
+
+    // A program halts when it runs out of the ROM or loops forever without reading the
+    // keyboard, like the usual `(END) @END 0;JMP` or a compiled `while (true) {}`
+    pub fn is_halted(&self) -> bool {
+        self.halt.halted || self.pc as usize >= self.rom.len()
+    }
+
+    // Sets the PC back to 0, keeps the RAM
+    pub fn reset(&mut self) {
+        self.pc = 0;
+        self.halt.reset();
+    }
+
+    pub fn ram(&self, address: usize) -> u16 {
+        self.ram[address % RAM_SIZE]
+    }
+
+    pub fn set_ram(&mut self, address: usize, value: u16) {
+        self.ram[address % RAM_SIZE] = value;
+        self.halt.reset();
+    }
+
+    // The whole RAM, SCREEN and KBD included
+    pub fn memory(&self) -> &[u16] {
+        &self.ram
+    }
+
+    pub fn rom(&self) -> &[u16] {
+        &self.rom
+    }
+
+    pub fn a(&self) -> u16 {
+        self.a
+    }
+
+    pub fn d(&self) -> u16 {
+        self.d
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn set_a(&mut self, value: u16) {
+        self.a = value;
+        self.halt.reset();
+    }
+
+    pub fn set_d(&mut self, value: u16) {
+        self.d = value;
+        self.halt.reset();
+    }
+
+    pub fn set_pc(&mut self, value: u16) {
+        self.pc = value;
+        self.halt.reset();
+    }
+
+    // Number of instructions executed since the start
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+}
+
+// Hack ALU, `control` holds the bits zx nx zy ny f no
+fn alu(x: u16, y: u16, control: u16) -> u16 {
+    let x = if control & 0x20 != 0 { 0 } else { x };
+    let x = if control & 0x10 != 0 { !x } else { x };
+    let y = if control & 0x08 != 0 { 0 } else { y };
+    let y = if control & 0x04 != 0 { !y } else { y };
+    let out = if control & 0x02 != 0 { x.wrapping_add(y) } else { x & y };
+    if control & 0x01 != 0 { !out } else { out }
+}
+
+// Reads the lines of a `.hack` file
+fn parse_hack(source: &str, file: &str) -> Result<Vec<u16>, EmulatorError> {
+    let mut rom = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.len() != 16 || !line.chars().all(|c| c == '0' || c == '1') {
+            return Err(EmulatorError::InvalidWord(file.to_string(), index + 1, line.to_string()));
+        }
+        rom.push(u16::from_str_radix(line, 2).unwrap());
+    }
+    Ok(rom)
+}
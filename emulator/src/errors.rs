@@ -0,0 +1,42 @@
+use hack_assembler::AsmError;
+use hvm::HVMError;
+use std::{error::Error, fmt, io};
+
+#[derive(Debug)]
+pub enum EmulatorError {
+    Io(String, io::Error), // file, cause
+    InvalidWord(String, usize, String), // file, line, word
+    Assembler(Vec<AsmError>),
+    Vm(Vec<HVMError>),
+    RomTooLarge(usize), // number of instructions
+    Timeout(u64), // cycles run without halting
+    Script(String, usize, String), // file, line, message
+}
+
+impl Error for EmulatorError {}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmulatorError::Io(file, err) => write!(f, "{}: {}", file, err),
+            EmulatorError::InvalidWord(file, line, word) => {
+                write!(f, "{}:{}: `{}` is not a 16-bit binary word", file, line, word)
+            }
+            EmulatorError::Assembler(errors) => {
+                let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}", messages.join("\n"))
+            }
+            EmulatorError::Vm(errors) => {
+                let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}", messages.join("\n"))
+            }
+            EmulatorError::RomTooLarge(size) => {
+                write!(f, "program has {} instructions, the ROM holds 32768", size)
+            }
+            EmulatorError::Timeout(cycles) => {
+                write!(f, "program did not halt after {} cycles", cycles)
+            }
+            EmulatorError::Script(file, line, message) => write!(f, "{}:{}: {}", file, line, message),
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use crate::cpu::{Cpu, KBD};
+use crate::errors::EmulatorError;
+use std::fs;
+
+// Hack key codes of the keys that aren't printable characters
+const SPECIAL_KEYS: [(&str, u16); 16] = [
+    ("NONE", 0),
+    ("SPACE", 32),
+    ("NEWLINE", 128),
+    ("ENTER", 128),
+    ("BACKSPACE", 129),
+    ("LEFT", 130),
+    ("UP", 131),
+    ("RIGHT", 132),
+    ("DOWN", 133),
+    ("HOME", 134),
+    ("END", 135),
+    ("PAGEUP", 136),
+    ("PAGEDOWN", 137),
+    ("INSERT", 138),
+    ("DELETE", 139),
+    ("ESC", 140),
+];
+
+// Timeline of key presses replayed into the KBD register.
+//
+// One event per line: `<cycle> <key>`, `//` starts a comment.
+// A key is a printable character (`a`, `7`), a name (`LEFT`, `ENTER`, `F1`...),
+// a code (`#131`) or `NONE` to release every key.
+#[derive(Debug, Clone, Default)]
+pub struct KeyboardScript {
+    events: Vec<(u64, u16)>, // cycle, key code (sorted by cycle)
+}
+
+impl KeyboardScript {
+    pub fn parse(source: &str, file: &str) -> Result<Self, EmulatorError> {
+        let mut events = Vec::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let line = line.split("//").next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: String| EmulatorError::Script(file.to_string(), index + 1, message);
+
+            let (cycle, key) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| error(format!("expected `<cycle> <key>`, found `{}`", line)))?;
+            let cycle = cycle
+                .parse::<u64>()
+                .map_err(|_| error(format!("invalid cycle `{}`", cycle)))?;
+            let key = key.trim();
+            let code = key_code(key).ok_or_else(|| error(format!("unknown key `{}`", key)))?;
+
+            events.push((cycle, code));
+        }
+
+        // Keeps the file order for events on the same cycle
+        events.sort_by_key(|&(cycle, _)| cycle);
+        Ok(Self { events })
+    }
+
+    pub fn load(path: &str) -> Result<Self, EmulatorError> {
+        let source = fs::read_to_string(path).map_err(|e| EmulatorError::Io(path.to_string(), e))?;
+        Self::parse(&source, path)
+    }
+
+    pub fn events(&self) -> &[(u64, u16)] {
+        &self.events
+    }
+}
+
+// Hack key code of a key of the script
+pub fn key_code(key: &str) -> Option<u16> {
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return (' '..='~').contains(&c).then_some(c as u16);
+    }
+    if let Some(code) = key.strip_prefix('#') {
+        return code.parse::<u16>().ok();
+    }
+
+    let name = key.to_uppercase();
+    if let Some(number) = name.strip_prefix('F') {
+        // F1 = 141 ... F12 = 152
+        if let Ok(number @ 1..=12) = number.parse::<u16>() {
+            return Some(140 + number);
+        }
+    }
+    SPECIAL_KEYS.iter().find(|(n, _)| *n == name).map(|&(_, code)| code)
+}
+
+// Runs `cpu` for `cycles` instructions and writes each key of `script` into KBD
+// when the cycle counter reaches its cycle. Returns the number of instructions executed.
+pub fn run_with_keyboard(cpu: &mut Cpu, script: &KeyboardScript, cycles: u64) -> u64 {
+    let start = cpu.cycles();
+    let end = start + cycles;
+
+    for &(cycle, code) in script.events() {
+        if cycle >= end {
+            break;
+        }
+        if cycle > cpu.cycles() {
+            cpu.run(cycle - cpu.cycles());
+            if cpu.is_halted() {
+                return cpu.cycles() - start;
+            }
+        }
+        cpu.set_ram(KBD, code);
+    }
+    cpu.run(end.saturating_sub(cpu.cycles()));
+
+    cpu.cycles() - start
+}
@@ -0,0 +1,11 @@
+mod cpu;
+mod errors;
+mod keyboard;
+mod screen;
+mod tst;
+
+pub use cpu::{Cpu, KBD, RAM_SIZE, ROM_SIZE, SCREEN};
+pub use errors::EmulatorError;
+pub use keyboard::{key_code, run_with_keyboard, KeyboardScript};
+pub use screen::{pixel, render_png, render_ppm, run_with_snapshots, save_screen, SCREEN_HEIGHT, SCREEN_WIDTH};
+pub use tst::{run_test_script, Mismatch, TestOutcome};
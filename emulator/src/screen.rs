@@ -0,0 +1,130 @@
+use crate::cpu::{Cpu, SCREEN};
+use crate::errors::EmulatorError;
+use std::fs;
+use std::path::Path;
+
+pub const SCREEN_WIDTH: usize = 512;
+pub const SCREEN_HEIGHT: usize = 256;
+const WORDS_PER_ROW: usize = SCREEN_WIDTH / 16;
+
+// Whether the pixel (x, y) is black.
+// Each row is 32 words, the least significant bit of a word is its leftmost pixel.
+pub fn pixel(memory: &[u16], x: usize, y: usize) -> bool {
+    let word = memory[SCREEN + y * WORDS_PER_ROW + x / 16];
+    word & (1 << (x % 16)) != 0
+}
+
+// Renders the screen memory map as a binary PPM (P6) image
+pub fn render_ppm(memory: &[u16]) -> Vec<u8> {
+    let mut image = format!("P6\n{} {}\n255\n", SCREEN_WIDTH, SCREEN_HEIGHT).into_bytes();
+    for y in 0..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            let color = if pixel(memory, x, y) { 0 } else { 255 };
+            image.extend_from_slice(&[color, color, color]);
+        }
+    }
+    image
+}
+
+// Renders the screen memory map as a 1-bit grayscale PNG image
+pub fn render_png(memory: &[u16]) -> Vec<u8> {
+    // Scanlines: filter type 0 then one bit per pixel, leftmost pixel in the most significant bit
+    let mut scanlines = Vec::with_capacity(SCREEN_HEIGHT * (1 + SCREEN_WIDTH / 8));
+    for y in 0..SCREEN_HEIGHT {
+        scanlines.push(0);
+        for column in 0..WORDS_PER_ROW {
+            // Hack: 1 is black, PNG grayscale: 1 is white
+            let word = !memory[SCREEN + y * WORDS_PER_ROW + column].reverse_bits();
+            scanlines.extend_from_slice(&word.to_be_bytes());
+        }
+    }
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&(SCREEN_WIDTH as u32).to_be_bytes());
+    header.extend_from_slice(&(SCREEN_HEIGHT as u32).to_be_bytes());
+    header.extend_from_slice(&[1, 0, 0, 0, 0]); // bit depth, grayscale, deflate, no filter, no interlace
+
+    let mut image = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+    write_chunk(&mut image, b"IHDR", &header);
+    write_chunk(&mut image, b"IDAT", &zlib_store(&scanlines));
+    write_chunk(&mut image, b"IEND", &[]);
+    image
+}
+
+// Writes the screen of `cpu` into `path`, as a PNG if the extension is `.png`, as a PPM otherwise
+pub fn save_screen(cpu: &Cpu, path: &str) -> Result<(), EmulatorError> {
+    let image = if Path::new(path).extension().is_some_and(|ext| ext == "png") {
+        render_png(cpu.memory())
+    } else {
+        render_ppm(cpu.memory())
+    };
+    fs::write(path, image).map_err(|e| EmulatorError::Io(path.to_string(), e))
+}
+
+// Runs `cpu` and saves the screen when the cycle counter reaches each value of `cycles`.
+// Snapshots are named after `path` with the cycle count appended: `pong.png` gives `pong_1000.png`.
+pub fn run_with_snapshots(cpu: &mut Cpu, cycles: &[u64], path: &str) -> Result<Vec<String>, EmulatorError> {
+    let mut cycles = cycles.to_vec();
+    cycles.sort_unstable();
+
+    let path = Path::new(path);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("screen");
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("ppm");
+
+    let mut files = Vec::new();
+    for cycle in cycles {
+        if cycle > cpu.cycles() {
+            cpu.run(cycle - cpu.cycles());
+        }
+        let file = path.with_file_name(format!("{}_{}.{}", stem, cycle, extension));
+        let file = file.to_string_lossy().into_owned();
+        save_screen(cpu, &file)?;
+        files.push(file);
+    }
+    Ok(files)
+}
+
+// Appends a PNG chunk: length, type, data, CRC of type and data
+fn write_chunk(image: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    image.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = image.len();
+    image.extend_from_slice(kind);
+    image.extend_from_slice(data);
+    let crc = crc32(&image[start..]);
+    image.extend_from_slice(&crc.to_be_bytes());
+}
+
+// zlib stream made of uncompressed deflate blocks
+fn zlib_store(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        stream.push(last as u8);
+        stream.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        stream.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
@@ -0,0 +1,596 @@
+use crate::cpu::Cpu;
+use crate::errors::EmulatorError;
+use hvm::Vm;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Stops `repeat` without a count and endless `while` loops
+const MAX_ITERATIONS: u64 = 100_000_000;
+
+// Runner for the nand2tetris CPUEmulator and VMEmulator test scripts (`.tst`).
+//
+// Supported commands: `load`, `output-file`, `compare-to`, `output-list`, `output`,
+// `set`, `repeat`, `while`, `ticktock`, `tick`, `tock`, `vmstep`, `echo`, `clear-echo`,
+// `breakpoint` and `clear-breakpoints` (the last four are accepted and ignored).
+// Variables: `RAM[i]`, `ROM[i]` (read only), `A`, `D`, `PC` and `time`.
+// A `.vm` file or a folder (the folder of the script without argument) loads a VM program,
+// its variables are `RAM[i]`, `sp`, `local`, `argument`, `this`, `that` and
+// `local[i]`, `argument[i]`, `this[i]`, `that[i]`, `temp[i]`.
+
+// Result of a test script
+#[derive(Debug, Clone, Default)]
+pub struct TestOutcome {
+    pub output: Vec<String>, // lines written by `output-list` and `output`
+    pub failure: Option<Mismatch>, // first line that differs from the compare file
+}
+
+// Line of the output that doesn't match the compare file (lines start at 1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub line: usize,
+    pub expected: String,
+    pub found: String,
+}
+
+impl TestOutcome {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Command {
+    Load(Option<String>),
+    OutputFile(String),
+    CompareTo(String),
+    OutputList(Vec<Column>),
+    Output,
+    Set(String, i32),
+    Tick,
+    Tock,
+    TickTock,
+    VmStep,
+    Ignored,
+    Repeat(Option<u64>, Vec<(usize, Command)>),
+    While(Condition, Vec<(usize, Command)>),
+}
+
+// `RAM[0]%D2.6.2`: variable, format, left padding, length, right padding
+#[derive(Debug, Clone)]
+struct Column {
+    variable: String,
+    format: char,
+    pad_left: usize,
+    len: usize,
+    pad_right: usize,
+}
+
+// `RAM[0] <> 0`
+#[derive(Debug, Clone)]
+struct Condition {
+    left: String,
+    operator: String,
+    right: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Text(String), // "quoted text"
+    Separator,    // `,` `;` or `!`
+    OpenBlock,
+    CloseBlock,
+}
+
+// Runs the test script at `path`, writes the `.out` file given by `output-file`
+// and compares every line to the `.cmp` file given by `compare-to`
+pub fn run_test_script(path: &str) -> Result<TestOutcome, EmulatorError> {
+    let source = fs::read_to_string(path).map_err(|e| EmulatorError::Io(path.to_string(), e))?;
+    let commands = parse_script(&source, path)?;
+
+    let directory = Path::new(path).parent().unwrap_or_else(|| Path::new(".")).to_path_buf();
+    let mut runner = Runner {
+        script: path.to_string(),
+        directory,
+        cpu: None,
+        vm: None,
+        columns: Vec::new(),
+        output_file: None,
+        compare: None,
+        outcome: TestOutcome::default(),
+    };
+    runner.execute(&commands)?;
+
+    if let Some(output_file) = &runner.output_file {
+        let mut content = runner.outcome.output.join("\n");
+        content.push('\n');
+        fs::write(output_file, content)
+            .map_err(|e| EmulatorError::Io(output_file.to_string_lossy().into_owned(), e))?;
+    }
+
+    Ok(runner.outcome)
+}
+
+struct Runner {
+    script: String,
+    directory: PathBuf,
+    cpu: Option<Cpu>,
+    vm: Option<Vm>,
+    columns: Vec<Column>,
+    output_file: Option<PathBuf>,
+    compare: Option<Vec<String>>,
+    outcome: TestOutcome,
+}
+
+impl Runner {
+    fn execute(&mut self, commands: &[(usize, Command)]) -> Result<(), EmulatorError> {
+        for (line, command) in commands {
+            // Stops at the first comparison failure, like the official tools
+            if self.outcome.failure.is_some() {
+                return Ok(());
+            }
+            self.execute_command(*line, command)?;
+        }
+        Ok(())
+    }
+
+    fn execute_command(&mut self, line: usize, command: &Command) -> Result<(), EmulatorError> {
+        let script = self.script.clone();
+        let error = |message: String| EmulatorError::Script(script.clone(), line, message);
+
+        match command {
+            Command::Load(file) => {
+                let path = match file {
+                    Some(file) => self.directory.join(file),
+                    None => self.directory.clone(),
+                };
+                let program = path.to_string_lossy();
+                if path.is_dir() || path.extension().is_some_and(|ext| ext == "vm") {
+                    self.vm = Some(Vm::load(&program).map_err(EmulatorError::Vm)?);
+                    self.cpu = None;
+                } else {
+                    self.cpu = Some(Cpu::load(&program)?);
+                    self.vm = None;
+                }
+            }
+            Command::OutputFile(file) => self.output_file = Some(self.directory.join(file)),
+            Command::CompareTo(file) => {
+                let path = self.directory.join(file);
+                let content = fs::read_to_string(&path)
+                    .map_err(|e| EmulatorError::Io(path.to_string_lossy().into_owned(), e))?;
+                self.compare = Some(content.lines().map(|l| l.trim_end().to_string()).collect());
+            }
+            Command::OutputList(columns) => {
+                self.columns = columns.clone();
+                let header = columns.iter().map(format_header).collect::<String>() + "|";
+                self.write_line(header);
+            }
+            Command::Output => {
+                let mut output = String::new();
+                for column in &self.columns {
+                    let value = self.get(&column.variable).map_err(error)?;
+                    output.push_str(&format_value(column, value));
+                }
+                output.push('|');
+                self.write_line(output);
+            }
+            Command::Set(variable, value) => self.set(variable, *value).map_err(error)?,
+            Command::Tick | Command::TickTock => self.cpu_mut().map_err(error)?.step(),
+            Command::Tock | Command::Ignored => {}
+            Command::VmStep => match &mut self.vm {
+                Some(vm) => vm.step(),
+                None => return Err(error("`vmstep` needs a VM program".to_string())),
+            },
+            Command::Repeat(count, body) => {
+                let count = count.unwrap_or(MAX_ITERATIONS);
+                for _ in 0..count {
+                    self.execute(body)?;
+                    if self.outcome.failure.is_some() {
+                        break;
+                    }
+                }
+            }
+            Command::While(condition, body) => {
+                let mut iterations = 0;
+                while self.check(condition).map_err(error)? {
+                    self.execute(body)?;
+                    iterations += 1;
+                    if self.outcome.failure.is_some() {
+                        break;
+                    }
+                    if iterations == MAX_ITERATIONS {
+                        return Err(EmulatorError::Timeout(iterations));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Appends a line to the output and compares it to the compare file
+    fn write_line(&mut self, line: String) {
+        let line_number = self.outcome.output.len() + 1;
+        if let Some(compare) = &self.compare {
+            let expected = compare.get(line_number - 1).cloned().unwrap_or_default();
+            if !matches_line(&expected, &line) {
+                self.outcome.failure = Some(Mismatch {
+                    line: line_number,
+                    expected,
+                    found: line.clone(),
+                });
+            }
+        }
+        self.outcome.output.push(line);
+    }
+
+    fn cpu(&self) -> Result<&Cpu, String> {
+        self.cpu.as_ref().ok_or_else(|| "no program loaded".to_string())
+    }
+
+    fn cpu_mut(&mut self) -> Result<&mut Cpu, String> {
+        self.cpu.as_mut().ok_or_else(|| "no program loaded".to_string())
+    }
+
+    // Reads a variable of the script
+    fn get(&self, variable: &str) -> Result<i16, String> {
+        if let Some(vm) = &self.vm {
+            let address = vm_address(vm, variable).ok_or_else(|| format!("unknown variable `{}`", variable))?;
+            return Ok(vm.ram(address) as i16);
+        }
+        let cpu = self.cpu()?;
+        let value = match variable {
+            "A" => cpu.a(),
+            "D" => cpu.d(),
+            "PC" => cpu.pc(),
+            "time" => cpu.cycles() as u16,
+            _ => match parse_indexed(variable) {
+                Some(("RAM", index)) => cpu.ram(index),
+                Some(("ROM", index)) => cpu.rom().get(index).copied().unwrap_or(0),
+                _ => return Err(format!("unknown variable `{}`", variable)),
+            },
+        };
+        Ok(value as i16)
+    }
+
+    // Writes a variable of the script
+    fn set(&mut self, variable: &str, value: i32) -> Result<(), String> {
+        let value = value as u16;
+        if let Some(vm) = &mut self.vm {
+            let address = vm_address(vm, variable).ok_or_else(|| format!("cannot set `{}`", variable))?;
+            vm.set_ram(address, value);
+            return Ok(());
+        }
+        let cpu = self.cpu_mut()?;
+        match variable {
+            "A" => cpu.set_a(value),
+            "D" => cpu.set_d(value),
+            "PC" => cpu.set_pc(value),
+            _ => match parse_indexed(variable) {
+                Some(("RAM", index)) => cpu.set_ram(index, value),
+                _ => return Err(format!("cannot set `{}`", variable)),
+            },
+        }
+        Ok(())
+    }
+
+    fn check(&self, condition: &Condition) -> Result<bool, String> {
+        let left = self.operand(&condition.left)?;
+        let right = self.operand(&condition.right)?;
+        match condition.operator.as_str() {
+            "=" => Ok(left == right),
+            "<>" => Ok(left != right),
+            "<" => Ok(left < right),
+            ">" => Ok(left > right),
+            "<=" => Ok(left <= right),
+            ">=" => Ok(left >= right),
+            operator => Err(format!("unknown operator `{}`", operator)),
+        }
+    }
+
+    // A number or a variable
+    fn operand(&self, operand: &str) -> Result<i32, String> {
+        match parse_value(operand) {
+            Some(value) => Ok(value),
+            None => self.get(operand).map(|v| v as i32),
+        }
+    }
+}
+
+// RAM address of a variable of a VM program
+fn vm_address(vm: &Vm, variable: &str) -> Option<usize> {
+    let pointer = |register: usize| vm.ram(register) as usize;
+    match variable {
+        "sp" => Some(0),
+        "local" => Some(1),
+        "argument" => Some(2),
+        "this" => Some(3),
+        "that" => Some(4),
+        _ => match parse_indexed(variable)? {
+            ("RAM", index) => Some(index),
+            ("local", index) => Some(pointer(1) + index),
+            ("argument", index) => Some(pointer(2) + index),
+            ("this", index) => Some(pointer(3) + index),
+            ("that", index) => Some(pointer(4) + index),
+            ("temp", index) if index < 8 => Some(5 + index),
+            _ => None,
+        },
+    }
+}
+
+// Splits `RAM[12]` into ("RAM", 12)
+fn parse_indexed(variable: &str) -> Option<(&str, usize)> {
+    let (name, index) = variable.strip_suffix(']')?.split_once('[')?;
+    Some((name, index.parse().ok()?))
+}
+
+// Parses `-5`, `%D-5`, `%X1F` or `%B101`
+fn parse_value(value: &str) -> Option<i32> {
+    let parsed = match value.get(..2) {
+        Some("%D") => value[2..].parse::<i32>().ok(),
+        Some("%X") => i32::from_str_radix(&value[2..], 16).ok(),
+        Some("%B") => i32::from_str_radix(&value[2..], 2).ok(),
+        _ => value.parse::<i32>().ok(),
+    };
+    parsed.filter(|v| (-32768..=65535).contains(v))
+}
+
+// Column name centered in the width of the column
+fn format_header(column: &Column) -> String {
+    let width = column.pad_left + column.len + column.pad_right;
+    let name: String = column.variable.chars().take(width).collect();
+    let left = (width - name.len()) / 2;
+    let right = width - name.len() - left;
+    format!("|{}{}{}", " ".repeat(left), name, " ".repeat(right))
+}
+
+fn format_value(column: &Column, value: i16) -> String {
+    let len = column.len;
+    let text = match column.format {
+        'X' => format!("{:04X}", value as u16),
+        'B' => format!("{:016b}", value as u16),
+        _ => value.to_string(),
+    };
+    // Keeps the rightmost digits of hexadecimal and binary values
+    let text = match column.format {
+        'X' | 'B' if text.len() > len => text[text.len() - len..].to_string(),
+        'X' | 'B' => format!("{:0>len$}", text),
+        'S' => format!("{:<len$}", text),
+        _ => format!("{:>len$}", text),
+    };
+    format!("|{}{}{}", " ".repeat(column.pad_left), text, " ".repeat(column.pad_right))
+}
+
+// `*` in the compare file matches any character
+fn matches_line(expected: &str, found: &str) -> bool {
+    expected.len() == found.len()
+        && expected.chars().zip(found.chars()).all(|(e, f)| e == '*' || e == f)
+}
+
+/*
+
+PARSER
+
+*/
+fn parse_script(source: &str, file: &str) -> Result<Vec<(usize, Command)>, EmulatorError> {
+    let tokens = tokenize(source);
+    let mut position = 0;
+    let commands = parse_block(&tokens, &mut position, file)?;
+    if let Some((line, _)) = tokens.get(position) {
+        return Err(EmulatorError::Script(file.to_string(), *line, "unexpected `}`".to_string()));
+    }
+    Ok(commands)
+}
+
+// Parses commands until the end of the script or a `}`
+fn parse_block(
+    tokens: &[(usize, Token)],
+    position: &mut usize,
+    file: &str,
+) -> Result<Vec<(usize, Command)>, EmulatorError> {
+    let mut commands = Vec::new();
+
+    while let Some((line, token)) = tokens.get(*position) {
+        let line = *line;
+        let error = |message: String| EmulatorError::Script(file.to_string(), line, message);
+        *position += 1;
+
+        let word = match token {
+            Token::Separator => continue,
+            Token::CloseBlock => {
+                *position -= 1;
+                break;
+            }
+            Token::Word(word) => word.as_str(),
+            Token::Text(text) => return Err(error(format!("unexpected text \"{}\"", text))),
+            Token::OpenBlock => return Err(error("unexpected `{`".to_string())),
+        };
+
+        // Arguments of the command, up to the next separator or block
+        let start = *position;
+        while let Some((_, Token::Word(_) | Token::Text(_))) = tokens.get(*position) {
+            *position += 1;
+        }
+        let args: Vec<&str> = tokens[start..*position]
+            .iter()
+            .map(|(_, token)| match token {
+                Token::Word(word) | Token::Text(word) => word.as_str(),
+                _ => "",
+            })
+            .collect();
+
+        let command = match word {
+            "load" => Command::Load(args.first().map(|a| a.to_string())),
+            "output-file" => Command::OutputFile(single_arg(&args, word).map_err(error)?),
+            "compare-to" => Command::CompareTo(single_arg(&args, word).map_err(error)?),
+            "output-list" => Command::OutputList(
+                args.iter().map(|a| parse_column(a)).collect::<Result<_, _>>().map_err(error)?,
+            ),
+            "output" => Command::Output,
+            "set" => match args.as_slice() {
+                [variable, value] => {
+                    let value = parse_value(value).ok_or_else(|| error(format!("invalid value `{}`", value)))?;
+                    Command::Set(variable.to_string(), value)
+                }
+                _ => return Err(error("expected `set <variable> <value>`".to_string())),
+            },
+            "tick" => Command::Tick,
+            "tock" => Command::Tock,
+            "ticktock" => Command::TickTock,
+            "vmstep" => Command::VmStep,
+            "echo" | "clear-echo" | "breakpoint" | "clear-breakpoints" => Command::Ignored,
+            "repeat" => {
+                let count = match args.as_slice() {
+                    [] => None,
+                    [count] => Some(count.parse::<u64>().map_err(|_| error(format!("invalid count `{}`", count)))?),
+                    _ => return Err(error("expected `repeat <count> {`".to_string())),
+                };
+                Command::Repeat(count, parse_body(tokens, position, file, line)?)
+            }
+            "while" => {
+                let condition = match args.as_slice() {
+                    [left, operator, right] => Condition {
+                        left: left.to_string(),
+                        operator: operator.to_string(),
+                        right: right.to_string(),
+                    },
+                    _ => return Err(error("expected `while <a> <operator> <b> {`".to_string())),
+                };
+                Command::While(condition, parse_body(tokens, position, file, line)?)
+            }
+            _ => return Err(error(format!("unknown command `{}`", word))),
+        };
+        commands.push((line, command));
+    }
+
+    Ok(commands)
+}
+
+// Parses `{ commands }`
+fn parse_body(
+    tokens: &[(usize, Token)],
+    position: &mut usize,
+    file: &str,
+    line: usize,
+) -> Result<Vec<(usize, Command)>, EmulatorError> {
+    let error = |message: &str| EmulatorError::Script(file.to_string(), line, message.to_string());
+    if tokens.get(*position).map(|(_, t)| t) != Some(&Token::OpenBlock) {
+        return Err(error("expected `{`"));
+    }
+    *position += 1;
+    let body = parse_block(tokens, position, file)?;
+    if tokens.get(*position).map(|(_, t)| t) != Some(&Token::CloseBlock) {
+        return Err(error("missing `}`"));
+    }
+    *position += 1;
+    Ok(body)
+}
+
+fn single_arg(args: &[&str], command: &str) -> Result<String, String> {
+    match args {
+        [arg] => Ok(arg.to_string()),
+        _ => Err(format!("`{}` expects one file", command)),
+    }
+}
+
+// Parses `RAM[0]%D2.6.2`, the format defaults to `%D1.6.1`
+fn parse_column(column: &str) -> Result<Column, String> {
+    let invalid = || format!("invalid output column `{}`", column);
+    let (variable, format) = match column.split_once('%') {
+        Some((variable, format)) => (variable, format),
+        None => (column, "D1.6.1"),
+    };
+
+    let mut chars = format.chars();
+    let kind = chars.next().filter(|c| "DXBS".contains(*c)).ok_or_else(invalid)?;
+    let sizes: Vec<usize> = chars
+        .as_str()
+        .split('.')
+        .map(|n| n.parse::<usize>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid())?;
+    match sizes.as_slice() {
+        [pad_left, len, pad_right] => Ok(Column {
+            variable: variable.to_string(),
+            format: kind,
+            pad_left: *pad_left,
+            len: *len,
+            pad_right: *pad_right,
+        }),
+        _ => Err(invalid()),
+    }
+}
+
+// Splits a script into tokens with their line number, removes comments
+fn tokenize(source: &str) -> Vec<(usize, Token)> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+    let mut word = String::new();
+
+    let flush = |word: &mut String, tokens: &mut Vec<(usize, Token)>, line: usize| {
+        if !word.is_empty() {
+            tokens.push((line, Token::Word(std::mem::take(word))));
+        }
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                flush(&mut word, &mut tokens, line);
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        line += 1;
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                flush(&mut word, &mut tokens, line);
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            '"' => {
+                flush(&mut word, &mut tokens, line);
+                let mut text = String::new();
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                    text.push(c);
+                }
+                tokens.push((line, Token::Text(text)));
+            }
+            ',' | ';' | '!' => {
+                flush(&mut word, &mut tokens, line);
+                tokens.push((line, Token::Separator));
+            }
+            '{' => {
+                flush(&mut word, &mut tokens, line);
+                tokens.push((line, Token::OpenBlock));
+            }
+            '}' => {
+                flush(&mut word, &mut tokens, line);
+                tokens.push((line, Token::CloseBlock));
+            }
+            c if c.is_whitespace() => {
+                flush(&mut word, &mut tokens, line);
+                if c == '\n' {
+                    line += 1;
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    flush(&mut word, &mut tokens, line);
+
+    tokens
+}
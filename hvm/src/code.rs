@@ -1,112 +1,139 @@
 use crate::asm;
 use crate::parser::{CommandType, MemorySegment};
-use rand;
-use std::io::Result;
-// use crate::errors::HVMError;
 
 // static STATIC_COUNT: u16 = 16;
 
+// Numbers the generated labels so that the same VM code always gives the same assembly
+#[derive(Debug, Default)]
+pub struct Context {
+    function_name: String, // function being translated
+    call_count: usize,     // calls made so far by this function
+    compare_count: usize,  // eq, gt and lt commands so far in the program
+    compact: bool,         // calls, returns and comparisons jump to the shared routines
+}
+
+impl Context {
+    pub fn new(compact: bool) -> Self {
+        Context { compact, ..Default::default() }
+    }
+}
+
 // Writes to the output file the assembly code that implements the given arithmetic-logical command.
-pub fn write_arithmetic(command: &str) -> Result<String> {
+pub fn write_arithmetic(context: &mut Context, command: &str) -> String {
     match command {
-        "add" => Ok(format!("{}", asm::ADD)),
-        "sub" => Ok(format!("{}", asm::SUB)),
-        "neg" => Ok(format!("{}", asm::NEG)),
-        "and" => Ok(format!("{}", asm::AND)),
-        "or" => Ok(format!("{}", asm::OR)),
-        "not" => Ok(format!("{}", asm::NOT)),
-        "eq" => {
-            let count1 = rand::random_range(1000..=99999);
-            let count2 = rand::random_range(1000..=99999);
-            Ok(format!(
-                "// eq\n@SP\nAM=M-1\nD=M\nA=A-1\nD=M-D\n@COUNT_{count1}\nD;JEQ\nD=0\n@COUNT_{count2}\n0;JMP\n(COUNT_{count1})\nD=-1\n(COUNT_{count2})\n@SP\nA=M-1\nM=D\n\n"
-            ))
-        }
-        "gt" => {
-            let count1 = rand::random_range(1000..=99999);
-            let count2 = rand::random_range(1000..=99999);
-            Ok(format!(
-                "// gt\n@SP\nAM=M-1\nD=M\nA=A-1\nD=M-D\n@COUNT_{count1}\nD;JGT\nD=0\n@COUNT_{count2}\n0;JMP\n(COUNT_{count1})\nD=-1\n(COUNT_{count2})\n@SP\nA=M-1\nM=D\n\n"
-            ))
-        }
-        "lt" => {
-            let count1 = rand::random_range(1000..=99999);
-            let count2 = rand::random_range(1000..=99999);
-            Ok(format!(
-                "// lt\n@SP\nAM=M-1\nD=M\nA=A-1\nD=M-D\n@COUNT_{count1}\nD;JLT\nD=0\n@COUNT_{count2}\n0;JMP\n(COUNT_{count1})\nD=-1\n(COUNT_{count2})\n@SP\nA=M-1\nM=D\n\n"
-            ))
-        }
-        _ => panic!("Unknow arithmetic command"),
+        "add" => format!("{}", asm::ADD),
+        "sub" => format!("{}", asm::SUB),
+        "neg" => format!("{}", asm::NEG),
+        "and" => format!("{}", asm::AND),
+        "or" => format!("{}", asm::OR),
+        "not" => format!("{}", asm::NOT),
+        "eq" => write_compare(context, "eq", "JEQ"),
+        "gt" => write_compare(context, "gt", "JGT"),
+        "lt" => write_compare(context, "lt", "JLT"),
+        _ => unreachable!("unknown arithmetic commands are rejected by the parser"),
+    }
+}
+
+// True is -1 and false 0
+fn write_compare(context: &mut Context, command: &str, jump: &str) -> String {
+    let count = context.compare_count;
+    context.compare_count += 1;
+    let name = command.to_uppercase();
+    if context.compact {
+        return format!("// {command}\n@{name}_RET_{count}\nD=A\n@$${name}\n0;JMP\n({name}_RET_{count})\n\n");
     }
+    // x - y is 0 only when x = y, even when it overflows
+    if command == "eq" {
+        return format!(
+            "// {command}\n@SP\nAM=M-1\nD=M\nA=A-1\nD=M-D\n@{name}_TRUE_{count}\nD;{jump}\nD=0\n@{name}_END_{count}\n0;JMP\n({name}_TRUE_{count})\nD=-1\n({name}_END_{count})\n@SP\nA=M-1\nM=D\n\n"
+        );
+    }
+    format!("// {command}\n{}@SP\nA=M-1\nM=D\n\n", write_signed_compare(jump, &format!("{name}_{count}")))
+}
+
+// Pops y, D = x > y (JGT) or x < y (JLT) with x on top of the stack, y in R13.
+// x - y overflows when x and y have different signs, then the sign of x is the result.
+fn write_signed_compare(jump: &str, label: &str) -> String {
+    // x >= 0 > y, then x < 0 <= y
+    let (x_positive, x_negative) = if jump == "JGT" { ("-1", "0") } else { ("0", "-1") };
+    format!(
+        "@SP\nAM=M-1\nD=M\n@R13\nM=D\n@SP\nA=M-1\nD=M\n@{label}_X_NEGATIVE\nD;JLT\n\
+         @R13\nD=M\n@{label}_SAME_SIGN\nD;JGE\nD={x_positive}\n@{label}_END\n0;JMP\n\
+         ({label}_X_NEGATIVE)\n@R13\nD=M\n@{label}_SAME_SIGN\nD;JLT\nD={x_negative}\n@{label}_END\n0;JMP\n\
+         ({label}_SAME_SIGN)\n@SP\nA=M-1\nD=M\n@R13\nD=D-M\n@{label}_TRUE\nD;{jump}\nD=0\n@{label}_END\n0;JMP\n\
+         ({label}_TRUE)\nD=-1\n({label}_END)\n"
+    )
 }
 
 // Write to the output file the assembly code that implements the given push or pop command.
-pub fn write_push_pop(command: &CommandType, filename: &str) -> Result<String> {
+pub fn write_push_pop(command: &CommandType, filename: &str) -> String {
     match command {
-        CommandType::Push(segment, index) => {
-            let code = match segment {
-                MemorySegment::Argument => write_push(index, "ARG", "argument"),
-                MemorySegment::Local => write_push(index, "LCL", "local"),
-                MemorySegment::Temp => write_push(index, "TEMP", "temp"),
-                MemorySegment::That => write_push(index, "THAT", "that"),
-                MemorySegment::This => write_push(index, "THIS", "this"),
-                MemorySegment::Static => write_push_static(filename, index),
-                MemorySegment::Constant => write_push_constant(index),
-                MemorySegment::Pointer => write_push_pointer(index),
-            };
-            return Ok(code);
-        }
-        CommandType::Pop(segment, index) => {
-            let code = match segment {
-                MemorySegment::Argument => write_pop(index, "ARG", "argument"),
-                MemorySegment::Local => write_pop(index, "LCL", "local"),
-                MemorySegment::Temp => write_pop(index, "TEMP", "temp"),
-                MemorySegment::That => write_pop(index, "THAT", "that"),
-                MemorySegment::This => write_pop(index, "THIS", "this"),
-                MemorySegment::Static => write_pop_static(filename, index),
-                MemorySegment::Pointer => write_pop_pointer(index),
-                _ => panic!("Segment unknow!"),
-            };
-            return Ok(code);
-        }
-        // _ => Err(HVMError::UnknownCommand("Unknow push/pop command")),
-        _ => panic!("Unknow push/pop command"),
+        CommandType::Push(segment, index) => match segment {
+            MemorySegment::Argument => write_push(index, "ARG", "argument"),
+            MemorySegment::Local => write_push(index, "LCL", "local"),
+            MemorySegment::Temp => write_push(index, "TEMP", "temp"),
+            MemorySegment::That => write_push(index, "THAT", "that"),
+            MemorySegment::This => write_push(index, "THIS", "this"),
+            MemorySegment::Static => write_push_static(filename, index),
+            MemorySegment::Constant => write_push_constant(index),
+            MemorySegment::Pointer => write_push_pointer(index),
+        },
+        CommandType::Pop(segment, index) => match segment {
+            MemorySegment::Argument => write_pop(index, "ARG", "argument"),
+            MemorySegment::Local => write_pop(index, "LCL", "local"),
+            MemorySegment::Temp => write_pop(index, "TEMP", "temp"),
+            MemorySegment::That => write_pop(index, "THAT", "that"),
+            MemorySegment::This => write_pop(index, "THIS", "this"),
+            MemorySegment::Static => write_pop_static(filename, index),
+            MemorySegment::Pointer => write_pop_pointer(index),
+            MemorySegment::Constant => unreachable!("`pop constant` is rejected by the parser"),
+        },
+        _ => unreachable!("only push and pop commands are written here"),
     }
 }
 
 // Writes assembly code that effects the label command
-pub fn write_label(func_name: &str, label: &str) -> Result<String> {
-    Ok(format!("({}${})\n",func_name, label))
+pub fn write_label(func_name: &str, label: &str) -> String {
+    format!("({}${})\n",func_name, label)
 }
 
 // Writes assembly code that effects the goto command
-pub fn write_goto(func_name: &str, label: &str) -> Result<String> {
-    Ok(format!("@{}${}\n0;JMP\n\n", func_name, label))
+pub fn write_goto(func_name: &str, label: &str) -> String {
+    format!("@{}${}\n0;JMP\n\n", func_name, label)
 }
 
 // Writes assembly code that effects the if-goto command
-pub fn write_if(func_name: &str, label: &str) -> Result<String> {
-    Ok(format!("// if-goto{}\n@{}${}\nD;JNE\n\n", asm::POP_Y, func_name, label))
+pub fn write_if(func_name: &str, label: &str) -> String {
+    format!("// if-goto{}\n@{}${}\nD;JNE\n\n", asm::POP_Y, func_name, label)
 }
 
 // Writes assembly code that effects the function command
-pub fn write_function(function_name: &str, nvars: u8) -> Result<String> {
+pub fn write_function(context: &mut Context, function_name: &str, nvars: u8) -> String {
+    context.function_name = function_name.to_string();
+    context.call_count = 0;
+
     // add label
     let label = format!("({})\n", function_name);
-    // init nvars local at 0
+    // push nvars locals initialized at 0, LCL already points at the first one
     let mut local_vars_init = String::new();
-    // local_vars_init.push_str("@1\nA=M\nM=0\n");
-    for _ in 1..nvars.clone() {
-        let init_local = format!("@1\nA=M+1\nM=0\n");
-        local_vars_init.push_str(&init_local);
+    for _ in 0..nvars {
+        local_vars_init.push_str("@SP\nA=M\nM=0\n@SP\nM=M+1\n");
     }
-    Ok(format!("{}{}\n", label, local_vars_init))
+    format!("{}{}\n", label, local_vars_init)
 }
 
-// TODO: test it
-// Writes assembly code that effects the call command
-pub fn write_call(function_name: &str, nargs: u8) -> Result<String> {
-    let return_label = format!("{}$ret{}", function_name, rand::random::<u8>());
+// Writes assembly code that effects the call command,
+// the return address is `<caller>$ret.<n>` for the n-th call of the caller
+pub fn write_call(context: &mut Context, function_name: &str, nargs: u8) -> String {
+    let return_label = format!("{}$ret.{}", context.function_name, context.call_count);
+    context.call_count += 1;
+
+    // R13 = nArgs, R14 = function, D = return address
+    if context.compact {
+        return format!(
+            "// call {function_name} {nargs}\n@{nargs}\nD=A\n@R13\nM=D\n@{function_name}\nD=A\n@R14\nM=D\n@{return_label}\nD=A\n@$$CALL\n0;JMP\n({return_label})\n\n"
+        );
+    }
 
     let save_return_address = format!("@{}\nD=A\n@SP\nA=M\nM=D\n@SP\nM=M+1\n", return_label);
 
@@ -124,7 +151,7 @@ pub fn write_call(function_name: &str, nargs: u8) -> Result<String> {
 
     let define_return_label = format!("({})\n", return_label);
 
-    Ok(format!(
+    format!(
         "// call {} {}\n{}{}{}{}{}{}{}{}{}\n",
         function_name,
         nargs,
@@ -137,16 +164,20 @@ pub fn write_call(function_name: &str, nargs: u8) -> Result<String> {
         reposition_lcl,
         goto_function,
         define_return_label
-    ))
+    )
 }
 
 // Writes assembly code that effects the return command
-pub fn write_return() -> Result<String> {
-    // Save LCL as `frame`
-    let save_frame = "// frame=LCL\n@LCL\nD=M\n@frame\nM=D\n";
+pub fn write_return(context: &Context) -> String {
+    if context.compact {
+        return "// return\n@$$RETURN\n0;JMP\n\n".to_string();
+    }
 
-    // Save retAddr (frame - 5) dans R14
-    let save_ret_addr = "// retAddr (frame - 5)\n@5\nD=D-A\nA=D\nD=M\n@ret\nM=D\n";
+    // Save LCL as `frame` in R13
+    let save_frame = "// frame=LCL\n@LCL\nD=M\n@R13\nM=D\n";
+
+    // Save retAddr (frame - 5) in R14
+    let save_ret_addr = "// retAddr (frame - 5)\n@5\nD=D-A\nA=D\nD=M\n@R14\nM=D\n";
     
     // ARG[0] = return value
     let get_last_values = "// *ARG=pop\n@SP\nM=M-1\nA=M\nD=M\n@ARG\nA=M\nM=D\n";
@@ -155,21 +186,21 @@ pub fn write_return() -> Result<String> {
     let updates_sp = "// SP=ARG[1]\n@ARG\nD=M+1\n@SP\nM=D\n";
 
     // THAT = *(frame - 1)
-    let updates_that = "// THAT = *(frame - 1)\n@frame\nD=M\n@1\nD=D-A\nA=D\nD=M\n@THAT\nM=D\n";
+    let updates_that = "// THAT = *(frame - 1)\n@R13\nD=M\n@1\nD=D-A\nA=D\nD=M\n@THAT\nM=D\n";
 
     // THIS = *(frame - 2)
-    let updates_this = "// THIS = *(frame - 2)\n@frame\nD=M\n@2\nD=D-A\nA=D\nD=M\n@THIS\nM=D\n";
+    let updates_this = "// THIS = *(frame - 2)\n@R13\nD=M\n@2\nD=D-A\nA=D\nD=M\n@THIS\nM=D\n";
 
     // ARG = *(frame - 3)
-    let updates_arg = "// ARG = *(frame - 3)\n@frame\nD=M\n@3\nD=D-A\nA=D\nD=M\n@ARG\nM=D\n";
+    let updates_arg = "// ARG = *(frame - 3)\n@R13\nD=M\n@3\nD=D-A\nA=D\nD=M\n@ARG\nM=D\n";
 
     // LCL = *(frame - 4)
-    let updates_lcl = "// LCL = *(frame - 4)\n@frame\nD=M\n@4\nD=D-A\nA=D\nD=M\n@LCL\nM=D\n";
+    let updates_lcl = "// LCL = *(frame - 4)\n@R13\nD=M\n@4\nD=D-A\nA=D\nD=M\n@LCL\nM=D\n";
 
     // Jump to retAdr
-    let jump_to_ret = "@ret\nA=M\n0;JMP\n";
+    let jump_to_ret = "@R14\nA=M\n0;JMP\n";
 
-    Ok(format!(
+    format!(
         "// return\n{}{}{}{}{}{}{}{}{}\n",
         save_frame,
         save_ret_addr,
@@ -180,11 +211,47 @@ pub fn write_return() -> Result<String> {
         updates_arg,
         updates_lcl,
         jump_to_ret
-    ))
+    )
+}
+
+// Shared call and return routines of the compact mode, a call site jumps to `$$CALL`
+// with the return address in D, nArgs in R13 and the function in R14
+pub fn write_shared_routines() -> String {
+    let push_d = "@SP\nAM=M+1\nA=A-1\nM=D\n";
+    let save_frame: String = ["LCL", "ARG", "THIS", "THAT"]
+        .iter()
+        .map(|register| format!("@{register}\nD=M\n{push_d}"))
+        .collect();
+    let call = format!(
+        "// $$CALL\n($$CALL)\n{push_d}{save_frame}// ARG=SP-(nArgs+5)\n@R13\nD=M\n@5\nD=D+A\n@SP\nD=M-D\n@ARG\nM=D\n// LCL=SP\n@SP\nD=M\n@LCL\nM=D\n@R14\nA=M\n0;JMP\n\n"
+    );
+
+    // frame in R13, return address in R14, the caller's THAT, THIS, ARG and LCL are below the frame
+    let restore: String = ["THAT", "THIS", "ARG", "LCL"]
+        .iter()
+        .map(|register| format!("@R13\nAM=M-1\nD=M\n@{register}\nM=D\n"))
+        .collect();
+    let r#return = format!(
+        "// $$RETURN\n($$RETURN)\n@LCL\nD=M\n@R13\nM=D\n@5\nA=D-A\nD=M\n@R14\nM=D\n// *ARG=pop\n@SP\nAM=M-1\nD=M\n@ARG\nA=M\nM=D\n// SP=ARG+1\n@ARG\nD=M+1\n@SP\nM=D\n{restore}@R14\nA=M\n0;JMP\n\n"
+    );
+
+    format!("{call}{}", r#return)
+}
+
+// Shared routine of the compact mode for `eq`, `gt` or `lt`, the return address is in D
+pub fn write_compare_routine(command: &str, jump: &str) -> String {
+    let name = command.to_uppercase();
+    if command != "eq" {
+        let compare = write_signed_compare(jump, &format!("$${name}"));
+        return format!("// $${name}\n($${name})\n@R15\nM=D\n{compare}@SP\nA=M-1\nM=D\n@R15\nA=M\n0;JMP\n\n");
+    }
+    format!(
+        "// $${name}\n($${name})\n@R15\nM=D\n@SP\nAM=M-1\nD=M\nA=A-1\nD=M-D\nM=-1\n@$${name}_TRUE\nD;{jump}\n@SP\nA=M-1\nM=0\n($${name}_TRUE)\n@R15\nA=M\n0;JMP\n\n"
+    )
 }
 
 // Generates bootstarp code & call Sys.init
-pub fn write_bootstrap() -> Result<String> {
+pub fn write_bootstrap() -> String {
     let init_sp = "@256\nD=A\n@SP\nM=D\n"; // SP = 256
     // let call_sys_init = write_call("Sys.init", 0)?; // Call Sys.init()
     let save_return_address = format!("@BOOTSTRAP\nD=A\n@SP\nA=M\nM=D\n@SP\nM=M+1\n");
@@ -202,7 +269,7 @@ pub fn write_bootstrap() -> Result<String> {
 
     let define_return_label = "(BOOTSTRAP)\n";
 
-    Ok(format!(
+    format!(
         "// Bootstrap Code\n{}\n{}{}{}{}{}{}{}{}{}\n",
         init_sp, 
         // call_sys_init,
@@ -215,7 +282,7 @@ pub fn write_bootstrap() -> Result<String> {
         reposition_lcl,
         goto_function,
         define_return_label
-    ))
+    )
 }
 
 /*
@@ -298,7 +365,7 @@ fn write_pop_pointer(index: &u16) -> String {
 }
 
 // Get the memory segment
-fn incremente_m(index: u16) -> String {
+pub(crate) fn incremente_m(index: u16) -> String {
     let mut increment = String::new();
 
     match index {
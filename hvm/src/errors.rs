@@ -1,17 +1,55 @@
-use std::{fmt, error::Error};
+use std::{error::Error, fmt, io};
+
+// Position of a command in its `.vm` file (line starts at 1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+}
 
 #[derive(Debug)]
 pub enum HVMError {
-  UnknownCommand(&'static str),
-
+    Io(String, io::Error), // file, cause
+    UnknownCommand(Location, String), // location, command
+    WrongArgumentCount(Location, String, usize), // location, command, expected arguments
+    UnknownSegment(Location, String), // location, command
+    InvalidNumber(Location, String), // location, command
+    IndexOutOfRange(Location, String, u16), // location, command, segment size
+    PopConstant(Location, String), // location, command
+    UnknownFunction(Location, String), // location, command
+    UnknownLabel(Location, String), // location, command
+    Timeout(u64), // commands run without halting
 }
 
 impl Error for HVMError {}
 
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
 impl fmt::Display for HVMError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            HVMError::UnknownCommand(msg) => write!(f, "Error: {}", msg),
+            HVMError::Io(file, err) => write!(f, "{}: {}", file, err),
+            HVMError::UnknownCommand(loc, command) => write!(f, "{}: unknown VM command `{}`", loc, command),
+            HVMError::WrongArgumentCount(loc, command, expected) => {
+                let name = command.split_whitespace().next().unwrap_or_default();
+                let plural = if *expected == 1 { "" } else { "s" };
+                write!(f, "{}: `{}`, {} expects {} argument{}", loc, command, name, expected, plural)
+            }
+            HVMError::UnknownSegment(loc, command) => write!(f, "{}: unknown memory segment in `{}`", loc, command),
+            HVMError::InvalidNumber(loc, command) => write!(f, "{}: invalid number in `{}`", loc, command),
+            HVMError::IndexOutOfRange(loc, command, size) => {
+                write!(f, "{}: index out of range in `{}` (the segment has {} words)", loc, command, size)
+            }
+            HVMError::PopConstant(loc, command) => {
+                write!(f, "{}: `{}` pops into the constant segment, which is read-only", loc, command)
+            }
+            HVMError::UnknownFunction(loc, command) => write!(f, "{}: unknown function in `{}`", loc, command),
+            HVMError::UnknownLabel(loc, command) => write!(f, "{}: unknown label in `{}`", loc, command),
+            HVMError::Timeout(steps) => write!(f, "program did not halt after {} commands", steps),
         }
     }
 }
@@ -3,5 +3,10 @@ mod translator;
 mod code;
 mod errors;
 mod asm;
+mod vm;
+mod optimizer;
 
-pub use translator::translate;
+pub use translator::{translate, translate_to, translate_with_library, translate_with_options, Options, RomUsage};
+pub use errors::{HVMError, Location};
+pub use parser::{parse_file, parse_source, CommandType, MemorySegment};
+pub use vm::{Vm, RAM_SIZE};
@@ -0,0 +1,74 @@
+use crate::code::incremente_m;
+use crate::parser::{CommandType, MemorySegment};
+
+// Peephole optimization: assembly code of two consecutive commands that don't need the stack
+// in between, None when the pair has no shorter translation.
+// Only the words above the stack pointer differ from the translation of each command.
+pub fn write_fused(first: &CommandType, second: &CommandType, filename: &str) -> Option<String> {
+    let code = match (first, second) {
+        // x + N, x - N in place
+        (CommandType::Push(MemorySegment::Constant, n), CommandType::Arithmetic(command)) => {
+            let operation = match command.as_str() {
+                "add" => "M=D+M",
+                "sub" => "M=M-D",
+                _ => return None,
+            };
+            format!("// push constant {n}; {command}\n@{n}\nD=A\n@SP\nA=M-1\n{operation}\n\n")
+        }
+        // y = x
+        (CommandType::Push(source, i), CommandType::Pop(target, j)) => format!(
+            "// push {source} {i}; pop {target} {j}\n{}{}\n",
+            load_d(source, *i, filename),
+            store_d(target, *j, filename)
+        ),
+        // Jumps if x is not 0
+        (CommandType::Push(source, i), CommandType::If(func_name, label)) => format!(
+            "// push {source} {i}; if-goto {label}\n{}@{func_name}${label}\nD;JNE\n\n",
+            load_d(source, *i, filename)
+        ),
+        // Jumps if !x is not 0: x is not -1
+        (CommandType::Arithmetic(command), CommandType::If(func_name, label)) if command == "not" => {
+            format!("// not; if-goto {label}\n@SP\nAM=M-1\nD=M+1\n@{func_name}${label}\nD;JNE\n\n")
+        }
+        _ => return None,
+    };
+    Some(code)
+}
+
+// D = the word `push segment index` pushes
+fn load_d(segment: &MemorySegment, index: u16, filename: &str) -> String {
+    match segment {
+        MemorySegment::Constant => format!("@{index}\nD=A\n"),
+        MemorySegment::Temp => format!("@{}\nD=M\n", index + 5),
+        MemorySegment::Pointer => format!("@{}\nD=M\n", pointer(index)),
+        MemorySegment::Static => format!("@{filename}.{index}\nD=M\n"),
+        _ if index <= 3 => format!("@{}\n{}D=M\n", base(segment), incremente_m(index)),
+        _ => format!("@{}\nD=M\n@{index}\nA=D+A\nD=M\n", base(segment)),
+    }
+}
+
+// The word `pop segment index` pops into = D
+fn store_d(segment: &MemorySegment, index: u16, filename: &str) -> String {
+    match segment {
+        MemorySegment::Temp => format!("@{}\nM=D\n", index + 5),
+        MemorySegment::Pointer => format!("@{}\nM=D\n", pointer(index)),
+        MemorySegment::Static => format!("@{filename}.{index}\nM=D\n"),
+        MemorySegment::Constant => unreachable!("`pop constant` is rejected by the parser"),
+        _ => format!("@{}\n{}M=D\n", base(segment), incremente_m(index)),
+    }
+}
+
+fn pointer(index: u16) -> &'static str {
+    if index == 0 { "THIS" } else { "THAT" }
+}
+
+// Register holding the base address of a segment
+fn base(segment: &MemorySegment) -> &'static str {
+    match segment {
+        MemorySegment::Local => "LCL",
+        MemorySegment::Argument => "ARG",
+        MemorySegment::This => "THIS",
+        MemorySegment::That => "THAT",
+        _ => unreachable!("only local, argument, this and that have a base register"),
+    }
+}
@@ -1,8 +1,12 @@
-use std::io::{self, BufRead};
-use std::{fs::File, path::PathBuf};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::errors::{HVMError, Location};
 
 const ARITHMETIC_COMMANDS: [&str; 9] = ["add", "sub", "neg", "eq", "gt", "lt", "and", "or", "not"];
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum MemorySegment {
     Local,
     Argument,
@@ -14,6 +18,7 @@ pub enum MemorySegment {
     Constant,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CommandType {
     Arithmetic(String),       // command
     Push(MemorySegment, u16), // arg1, arg2
@@ -27,99 +32,207 @@ pub enum CommandType {
     NewFile(String),          // Filename
 }
 
-// Parses a given file into a Vec<CommandType>
-pub fn parse_file(files: Vec<PathBuf>) -> io::Result<Vec<CommandType>> {
+// VM code of the command
+impl fmt::Display for CommandType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandType::Arithmetic(command) => write!(f, "{}", command),
+            CommandType::Push(segment, index) => write!(f, "push {} {}", segment, index),
+            CommandType::Pop(segment, index) => write!(f, "pop {} {}", segment, index),
+            CommandType::Label(_, label) => write!(f, "label {}", label),
+            CommandType::Goto(_, label) => write!(f, "goto {}", label),
+            CommandType::If(_, label) => write!(f, "if-goto {}", label),
+            CommandType::Function(name, nvars) => write!(f, "function {} {}", name, nvars),
+            CommandType::Call(name, nargs) => write!(f, "call {} {}", name, nargs),
+            CommandType::Return => write!(f, "return"),
+            CommandType::NewFile(name) => write!(f, "// {}.vm", name),
+        }
+    }
+}
+
+impl fmt::Display for MemorySegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MemorySegment::Local => "local",
+            MemorySegment::Argument => "argument",
+            MemorySegment::This => "this",
+            MemorySegment::That => "that",
+            MemorySegment::Temp => "temp",
+            MemorySegment::Pointer => "pointer",
+            MemorySegment::Static => "static",
+            MemorySegment::Constant => "constant",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// A command and the line it comes from, line 0 for `NewFile`
+pub(crate) type Located = (Location, CommandType);
+
+// Parses the given files into a Vec<CommandType>, reports the errors of every file
+pub fn parse_file(files: Vec<PathBuf>) -> Result<Vec<CommandType>, Vec<HVMError>> {
+    parse_files_located(files).map(without_locations)
+}
+
+// Parses like `parse_file`, keeps the location of every command
+pub(crate) fn parse_files_located(files: Vec<PathBuf>) -> Result<Vec<Located>, Vec<HVMError>> {
     let mut commands = Vec::new();
+    let mut errors = Vec::new();
 
     for file in files {
-        let f = File::open(&file)?;
-        let reader = io::BufReader::new(f);
-
-        let mut func_name = String::new();
-
-        commands.push(CommandType::NewFile(get_file_name(&file)));
-
-        for line in reader.lines() {
-            let line = line?;
-            let line = line.split("//").next().unwrap().trim().to_string(); // Remove comments
-
-            if line.is_empty() {
-                continue;
-            } else if ARITHMETIC_COMMANDS.contains(&line.as_str()) {
-                commands.push(CommandType::Arithmetic(line));
-            } else if line.starts_with("push") {
-                let (segment, index) = get_args(&line);
-                commands.push(CommandType::Push(segment, index));
-            } else if line.starts_with("pop") {
-                let (segment, index) = get_args(&line);
-                commands.push(CommandType::Pop(segment, index));
-            } else if line.starts_with("function") {
-                let (function_name, nvars) = get_function_name_and_nvars(&line);
-                func_name = function_name.clone();
-                commands.push(CommandType::Function(function_name, nvars));
-            } else if line.starts_with("call") {
-                let (function_name, nargs) = get_function_name_and_nvars(&line);
-                commands.push(CommandType::Call(function_name, nargs));
-            } else if line.starts_with("return") {
-                commands.push(CommandType::Return);
-            } else if line.starts_with("goto") {
-                let label = handle_label(&line);
-                commands.push(CommandType::Goto(func_name.clone(), label));
-            } else if line.starts_with("if-goto") {
-                let label = handle_label(&line);
-                commands.push(CommandType::If(func_name.clone(), label));
-            } else if line.starts_with("label") {
-                let label = handle_label(&line);
-                commands.push(CommandType::Label(func_name.clone(), label));
+        let name = file.to_string_lossy().into_owned();
+        match fs::read_to_string(&file) {
+            Ok(source) => match parse_source_located(&name, &source) {
+                Ok(file_commands) => commands.extend(file_commands),
+                Err(file_errors) => errors.extend(file_errors),
+            },
+            Err(e) => errors.push(HVMError::Io(name, e)),
+        }
+    }
+
+    if errors.is_empty() { Ok(commands) } else { Err(errors) }
+}
+
+// Parses the VM code of `file`, its name without extension prefixes the static variables
+pub fn parse_source(file: &str, source: &str) -> Result<Vec<CommandType>, Vec<HVMError>> {
+    parse_source_located(file, source).map(without_locations)
+}
+
+// Parses like `parse_source`, keeps the location of every command
+pub(crate) fn parse_source_located(file: &str, source: &str) -> Result<Vec<Located>, Vec<HVMError>> {
+    let mut commands = Vec::new();
+    let mut errors = Vec::new();
+    let mut func_name = String::new();
+
+    let start = Location { file: file.to_string(), line: 0 };
+    commands.push((start, CommandType::NewFile(get_file_name(Path::new(file)))));
+
+    for (number, line) in source.lines().enumerate() {
+        let line = line.split("//").next().unwrap().trim(); // Remove comments
+        if line.is_empty() {
+            continue;
+        }
+
+        let location = Location { file: file.to_string(), line: number + 1 };
+        match parse_command(line, &func_name, location.clone()) {
+            Ok(command) => {
+                if let CommandType::Function(function_name, _) = &command {
+                    func_name = function_name.clone();
+                }
+                commands.push((location, command));
             }
+            Err(e) => errors.push(e),
         }
     }
 
-    Ok(commands)
+    if errors.is_empty() { Ok(commands) } else { Err(errors) }
 }
 
-/// Get arguments
-/// example: push constant 7
-/// returns (MemorySegment::Constant, 7)
-fn get_args(line: &str) -> (MemorySegment, u16) {
+pub(crate) fn without_locations(commands: Vec<Located>) -> Vec<CommandType> {
+    commands.into_iter().map(|(_, command)| command).collect()
+}
+
+// Parses one command of the function `func_name`
+fn parse_command(line: &str, func_name: &str, location: Location) -> Result<CommandType, HVMError> {
     let args: Vec<&str> = line.split_whitespace().collect();
-    let segment = match args[1] {
-        "local" => MemorySegment::Local,
-        "argument" => MemorySegment::Argument,
-        "this" => MemorySegment::This,
-        "that" => MemorySegment::That,
-        "temp" => MemorySegment::Temp,
-        "pointer" => MemorySegment::Pointer,
-        "static" => MemorySegment::Static,
-        "constant" => MemorySegment::Constant,
-        _ => panic!("Segment unknow!"),
+    let expect_args = |count: usize| {
+        if args.len() == count + 1 {
+            Ok(())
+        } else {
+            Err(HVMError::WrongArgumentCount(location.clone(), line.to_string(), count))
+        }
     };
 
-    (segment, args[2].parse::<u16>().unwrap())
+    let command = match args[0] {
+        command if ARITHMETIC_COMMANDS.contains(&command) => {
+            expect_args(0)?;
+            CommandType::Arithmetic(command.to_string())
+        }
+        "push" => {
+            expect_args(2)?;
+            let (segment, index) = get_args(&args, line, &location)?;
+            CommandType::Push(segment, index)
+        }
+        "pop" => {
+            expect_args(2)?;
+            let (segment, index) = get_args(&args, line, &location)?;
+            if let MemorySegment::Constant = segment {
+                return Err(HVMError::PopConstant(location, line.to_string()));
+            }
+            CommandType::Pop(segment, index)
+        }
+        "function" => {
+            expect_args(2)?;
+            let (function_name, nvars) = get_function_name_and_nvars(&args, line, &location)?;
+            CommandType::Function(function_name, nvars)
+        }
+        "call" => {
+            expect_args(2)?;
+            let (function_name, nargs) = get_function_name_and_nvars(&args, line, &location)?;
+            CommandType::Call(function_name, nargs)
+        }
+        "return" => {
+            expect_args(0)?;
+            CommandType::Return
+        }
+        "goto" => {
+            expect_args(1)?;
+            CommandType::Goto(func_name.to_string(), args[1].to_string())
+        }
+        "if-goto" => {
+            expect_args(1)?;
+            CommandType::If(func_name.to_string(), args[1].to_string())
+        }
+        "label" => {
+            expect_args(1)?;
+            CommandType::Label(func_name.to_string(), args[1].to_string())
+        }
+        _ => return Err(HVMError::UnknownCommand(location, line.to_string())),
+    };
+    Ok(command)
 }
 
-// Get label
-// example: label N_LT_2 
-// Returns: N_LT_2 
-fn handle_label(line: &str) -> String {
-  let args: Vec<&str> = line.split_whitespace().collect();
-  args[1].to_string()
+/// Get arguments
+/// example: push constant 7
+/// returns (MemorySegment::Constant, 7)
+fn get_args(args: &[&str], line: &str, location: &Location) -> Result<(MemorySegment, u16), HVMError> {
+    // Segment and its number of words
+    let (segment, size) = match args[1] {
+        "local" => (MemorySegment::Local, None),
+        "argument" => (MemorySegment::Argument, None),
+        "this" => (MemorySegment::This, None),
+        "that" => (MemorySegment::That, None),
+        "temp" => (MemorySegment::Temp, Some(8)),
+        "pointer" => (MemorySegment::Pointer, Some(2)),
+        "static" => (MemorySegment::Static, None),
+        "constant" => (MemorySegment::Constant, Some(32768)),
+        _ => return Err(HVMError::UnknownSegment(location.clone(), line.to_string())),
+    };
+
+    let index = args[2]
+        .parse::<u16>()
+        .map_err(|_| HVMError::InvalidNumber(location.clone(), line.to_string()))?;
+    match size {
+        Some(size) if index >= size => Err(HVMError::IndexOutOfRange(location.clone(), line.to_string(), size)),
+        _ => Ok((segment, index)),
+    }
 }
 
 // Get functionName and nVars
 // Example: function Main.fibonacci 0
 // returns (Main.fibonacci, O)
-fn get_function_name_and_nvars(line: &str) -> (String, u8) {
-  let args: Vec<&str> = line.split_whitespace().collect();
-  let function_name = args[1].to_string();
-  let nvars = args[2].parse::<u8>().unwrap();
-  (function_name, nvars)
+fn get_function_name_and_nvars(args: &[&str], line: &str, location: &Location) -> Result<(String, u8), HVMError> {
+    let function_name = args[1].to_string();
+    let nvars = args[2]
+        .parse::<u8>()
+        .map_err(|_| HVMError::InvalidNumber(location.clone(), line.to_string()))?;
+    Ok((function_name, nvars))
 }
 
 // Get Filename
-fn get_file_name(file: &PathBuf) -> String {
+fn get_file_name(file: &Path) -> String {
     file.file_stem()
-        .unwrap_or_else(|| file.as_os_str())
+        .unwrap_or(file.as_os_str())
         .to_string_lossy()
         .into_owned()
-}
\ No newline at end of file
+}
@@ -1,86 +1,210 @@
 use crate::code::{
-    write_bootstrap, write_call, write_function, write_goto, write_if, write_label, write_return,
+    Context, write_bootstrap, write_call, write_compare_routine, write_function, write_goto, write_if, write_label,
+    write_return, write_shared_routines,
 };
 use crate::{
     code::{write_arithmetic, write_push_pop},
-    parser::{CommandType, parse_file},
+    parser::{CommandType, Located, parse_files_located, parse_source_located, without_locations},
 };
-use std::io::Write;
+use crate::errors::HVMError;
+use crate::optimizer::write_fused;
+use std::collections::HashSet;
 use std::path::Path;
 use std::fs;
-use std::{fs::File, path::PathBuf};
+use std::path::PathBuf;
 
-pub fn translate(input: &str) -> std::io::Result<()> {
+// Options of the translation
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub compact: bool,   // calls, returns and comparisons jump to shared routines instead of being inlined
+    pub optimize: bool,  // fuses common pairs of commands (peephole optimization)
+    pub rom_usage: bool, // also translates in the other mode to compare the sizes
+}
 
-    let (files, mut output) = handle_path(input)?;
+// Size of the translated program in Hack instructions, the ROM holds 32768
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RomUsage {
+    pub inline: usize,  // calls, returns and comparisons inlined
+    pub compact: usize, // with the shared routines
+}
 
-    let mut file_name = String::new();
+// Translates a `.vm` file or a folder of `.vm` files into `<name>.asm` next to it
+pub fn translate(input: &str) -> Result<(), Vec<HVMError>> {
+    translate_to(input, None)
+}
 
-    // Getting all instructions from the parser
-    let instructions = parse_file(files)?;
+// Translates a `.vm` file or a folder of `.vm` files into `output`,
+// or into `<name>.asm` next to the input when `output` is None
+pub fn translate_to(input: &str, output: Option<&str>) -> Result<(), Vec<HVMError>> {
+    translate_with_library(input, output, &[])
+}
 
-    // Adding bootstrap code
-    let bootstrap_code = write_bootstrap()?;
-    write!(output, "{}", bootstrap_code)?;
+// Translates like `translate_to`, with the VM classes of `library`
+// (file name without extension, VM code) added to the files of `input`.
+// Reports the errors of every file, the output is only written without errors.
+pub fn translate_with_library(input: &str, output: Option<&str>, library: &[(String, String)]) -> Result<(), Vec<HVMError>> {
+    translate_with_options(input, output, library, &Options::default()).map(|_| ())
+}
 
-    // Translate instructions into asm commands
-    for instruction in instructions {
-        let mut code: String = String::new();
-        match instruction {
-            CommandType::NewFile(fname) => {
-                file_name = fname
-            },
-            CommandType::Arithmetic(command) => {
-                let cmd = write_arithmetic(&command)?;
-                code = format!("{}", cmd)
-            }
-            CommandType::Push(_, _) => {
-                let cmd = write_push_pop(&instruction, &file_name)?;
-                code = format!("{}", cmd)
-            }
-            CommandType::Pop(_, _) => {
-                let cmd = write_push_pop(&instruction, &file_name)?;
-                code = format!("{}", cmd)
+// Translates like `translate_with_library` with `options`,
+// returns the size of the program in both modes when `options.rom_usage` is set
+pub fn translate_with_options(
+    input: &str,
+    output: Option<&str>,
+    library: &[(String, String)],
+    options: &Options,
+) -> Result<Option<RomUsage>, Vec<HVMError>> {
+    let (files, output_path) = handle_path(input, output).map_err(|e| vec![HVMError::Io(input.to_string(), e)])?;
+    let instructions = parse_program(files, library)?;
+
+    let asm = write_asm(&instructions, options);
+    let usage = options.rom_usage.then(|| {
+        let other = rom_size(&write_asm(&instructions, &Options { compact: !options.compact, ..options.clone() }));
+        let (inline, compact) = if options.compact { (other, rom_size(&asm)) } else { (rom_size(&asm), other) };
+        RomUsage { inline, compact }
+    });
+
+    let output_name = output_path.to_string_lossy().into_owned();
+    fs::write(&output_path, asm).map_err(|e| vec![HVMError::Io(output_name, e)])?;
+    Ok(usage)
+}
+
+// Commands of `files` followed by the ones of `library`, reports the errors of every file,
+// then the jumps and calls without target
+pub(crate) fn parse_program(files: Vec<PathBuf>, library: &[(String, String)]) -> Result<Vec<CommandType>, Vec<HVMError>> {
+    let mut instructions = Vec::new();
+    let mut errors = Vec::new();
+    match parse_files_located(files) {
+        Ok(commands) => instructions.extend(commands),
+        Err(e) => errors.extend(e),
+    }
+    for (name, code) in library {
+        match parse_source_located(&format!("{}.vm", name), code) {
+            Ok(commands) => instructions.extend(commands),
+            Err(e) => errors.extend(e),
+        }
+    }
+    if errors.is_empty() {
+        errors = resolve(&instructions);
+    }
+    if errors.is_empty() { Ok(without_locations(instructions)) } else { Err(errors) }
+}
+
+// Jumps to a label missing from their function and calls to functions the program doesn't define:
+// the assembler would take the missing labels for variables
+fn resolve(commands: &[Located]) -> Vec<HVMError> {
+    let mut functions = HashSet::new();
+    let mut labels = HashSet::new();
+    for (_, command) in commands {
+        match command {
+            CommandType::Function(name, _) => {
+                functions.insert(name.as_str());
             }
-            CommandType::Call(function_name, nargs) => {
-                let cmd = write_call(&function_name, nargs)?;
-                code = format!("{}", cmd)
+            CommandType::Label(function, label) => {
+                labels.insert((function.as_str(), label.as_str()));
             }
-            CommandType::Function(function_name, nvars) => {
-                let cmd = write_function(&function_name, nvars)?;
-                code = format!("{}", cmd)
+            _ => {}
+        }
+    }
+
+    let mut errors = Vec::new();
+    for (location, command) in commands {
+        match command {
+            CommandType::Goto(function, label) | CommandType::If(function, label)
+                if !labels.contains(&(function.as_str(), label.as_str())) =>
+            {
+                errors.push(HVMError::UnknownLabel(location.clone(), command.to_string()));
             }
-            CommandType::Goto(func_name, label) => {
-                let cmd = write_goto(&func_name, &label)?;
-                code = format!("{}", cmd)
+            CommandType::Call(name, _) if !functions.contains(name.as_str()) => {
+                errors.push(HVMError::UnknownFunction(location.clone(), command.to_string()));
             }
-            CommandType::If(func_name, label) => {
-                let cmd = write_if(&func_name, &label)?;
-                code = format!("{}", cmd)
+            _ => {}
+        }
+    }
+    errors
+}
+
+// Assembly code of `instructions` with `options`
+fn write_asm(instructions: &[CommandType], options: &Options) -> String {
+    let mut output = String::new();
+
+    let mut file_name = String::new();
+    let mut context = Context::new(options.compact);
+
+    // Adding bootstrap code when there is a Sys.init to call, the test scripts of a single
+    // file or of a program without Sys.vm set SP and the segments themselves
+    let has_sys_init = instructions
+        .iter()
+        .any(|i| matches!(i, CommandType::Function(name, _) if name == "Sys.init"));
+    if has_sys_init {
+        output.push_str(&write_bootstrap());
+    }
+
+    // The call and return routines, and the comparisons the program uses.
+    // Without bootstrap the program starts at address 0 and jumps over them.
+    if options.compact {
+        if !has_sys_init {
+            output.push_str("@$$START\n0;JMP\n\n");
+        }
+        output.push_str(&write_shared_routines());
+        for (command, jump) in [("eq", "JEQ"), ("gt", "JGT"), ("lt", "JLT")] {
+            if instructions.iter().any(|i| matches!(i, CommandType::Arithmetic(c) if c == command)) {
+                output.push_str(&write_compare_routine(command, jump));
             }
-            CommandType::Label(func_name, label) => {
-                let cmd = write_label(&func_name, &label)?;
-                code = format!("{}", cmd)
+        }
+        if !has_sys_init {
+            output.push_str("($$START)\n");
+        }
+    }
+
+    // Translate instructions into asm commands
+    let mut instructions = instructions.iter().peekable();
+    while let Some(instruction) = instructions.next() {
+        if options.optimize {
+            let fused = instructions.peek().and_then(|next| write_fused(instruction, next, &file_name));
+            if let Some(code) = fused {
+                output.push_str(&code);
+                instructions.next();
+                continue;
             }
-            CommandType::Return => {
-                let cmd = write_return()?;
-                code = format!("{}", cmd)
+        }
+
+        let code = match instruction {
+            CommandType::NewFile(fname) => {
+                file_name = fname.clone();
+                continue;
             }
+            CommandType::Arithmetic(command) => write_arithmetic(&mut context, command),
+            CommandType::Push(_, _) | CommandType::Pop(_, _) => write_push_pop(instruction, &file_name),
+            CommandType::Call(function_name, nargs) => write_call(&mut context, function_name, *nargs),
+            CommandType::Function(function_name, nvars) => write_function(&mut context, function_name, *nvars),
+            CommandType::Goto(func_name, label) => write_goto(func_name, label),
+            CommandType::If(func_name, label) => write_if(func_name, label),
+            CommandType::Label(func_name, label) => write_label(func_name, label),
+            CommandType::Return => write_return(&context),
         };
-        write!(output, "{}", code)?;
+        output.push_str(&code);
     }
 
-    // Adding an infinite end loop at the end of the file
-    // let _ = write!(output, "{}", format!("// End\n(END)\n@END\n0;JMP\n"));
+    output
+}
 
-    Ok(())
+// Number of instructions of `asm`, labels and comments excluded
+fn rom_size(asm: &str) -> usize {
+    asm.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("//") && !line.starts_with('('))
+        .count()
 }
 
 // Handles path
-fn handle_path(input: &str) -> std::io::Result<(Vec<PathBuf>, File)> {
+fn handle_path(input: &str, output: Option<&str>) -> std::io::Result<(Vec<PathBuf>, PathBuf)> {
+    let files = source_files(input)?;
     let path = Path::new(input);
 
-    let output_path = if path.is_dir() {
+    let output_path = if let Some(output) = output {
+        PathBuf::from(output)
+    } else if path.is_dir() {
         let filename = path
             .file_name()
             .and_then(|f| f.to_str())
@@ -95,7 +219,15 @@ fn handle_path(input: &str) -> std::io::Result<(Vec<PathBuf>, File)> {
         parent.join(format!("{}.asm", file_stem))
     };
 
-    let output = File::create(&output_path)?;
+    Ok((files, output_path))
+}
+
+// `.vm` files of `input`, a file or a folder, Sys.vm comes last
+pub(crate) fn source_files(input: &str) -> std::io::Result<Vec<PathBuf>> {
+    let path = Path::new(input);
+    if !path.exists() {
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "Invalid source path"));
+    }
 
     let mut files: Vec<PathBuf> = Vec::new();
 
@@ -135,5 +267,5 @@ fn handle_path(input: &str) -> std::io::Result<(Vec<PathBuf>, File)> {
     }
 });
 
-    Ok((files, output))
+    Ok(files)
 }
@@ -0,0 +1,483 @@
+use std::collections::HashMap;
+
+use crate::errors::HVMError;
+use crate::parser::{CommandType, MemorySegment};
+use crate::translator::{parse_program, source_files};
+
+// Words of the RAM, SCREEN and KBD included
+pub const RAM_SIZE: usize = 32768;
+
+const SP: usize = 0;
+const LCL: usize = 1;
+const ARG: usize = 2;
+const THIS: usize = 3;
+const THAT: usize = 4;
+const TEMP: u16 = 5;
+const STATIC: u16 = 16;
+const STACK: u16 = 256;
+const KBD: usize = 24576;
+
+// Most words a loop can write and still be seen as a halt
+const MAX_LOOP_WRITES: usize = 64;
+
+// Interpreter of VM programs, the VM emulator.
+//
+// The RAM is used like by the assembly of `translate`: SP, LCL, ARG, THIS and THAT in
+// RAM[0..5], temp in RAM[5..13], statics from RAM[16] in the order they first appear,
+// the stack from RAM[256] and the same frames pushed by calls. Only the return addresses
+// differ: they are command numbers instead of ROM addresses.
+// A program with `Sys.init` starts like the bootstrap code, the others start at their
+// first command with an empty RAM. Labels are not commands, `step` never stops on them.
+#[derive(Debug, Clone)]
+pub struct Vm {
+    ram: Vec<u16>,
+    program: Vec<Instruction>,
+    commands: Vec<CommandType>, // command of each instruction
+    functions: Vec<Function>,
+    statics: HashMap<(String, u16), u16>, // (file, index) -> address
+    calls: Vec<usize>,                    // functions being run, the current one last
+    called: bool,                         // the next `function` is entered by a call
+    pc: usize,
+    steps: u64,
+    halt: HaltCheck,
+}
+
+// Detects the endless loop that ends a program, like the CPU emulator: a backward jump
+// taken twice from the same function with the same RAM, without reading the keyboard
+#[derive(Debug, Clone, Default)]
+struct HaltCheck {
+    state: Option<(usize, usize)>, // target and call depth of the last backward jump
+    written: Vec<(usize, u16)>,    // words written since then, with their value at the jump
+    halted: bool,
+}
+
+impl HaltCheck {
+    // `address` holding `value` is about to be written
+    fn write(&mut self, address: usize, value: u16) {
+        if self.state.is_none() || self.written.iter().any(|&(written, _)| written == address) {
+            return;
+        }
+        if self.written.len() == MAX_LOOP_WRITES {
+            self.state = None;
+        } else {
+            self.written.push((address, value));
+        }
+    }
+
+    fn backward_jump(&mut self, state: (usize, usize), ram: &[u16]) {
+        if self.state == Some(state) && self.written.iter().all(|&(address, value)| ram[address] == value) {
+            self.halted = true;
+        } else {
+            self.state = Some(state);
+            self.written.clear();
+        }
+    }
+
+    // The RAM changed from outside the program, or the program read the keyboard
+    fn reset(&mut self) {
+        self.state = None;
+        self.written.clear();
+        self.halted = false;
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Function {
+    name: String,
+    file: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Instruction {
+    Push(Operand),
+    Pop(Operand),
+    Arithmetic(Operation),
+    Goto(usize),
+    IfGoto(usize),
+    Function(usize, u8), // function, nVars
+    Call(usize, u8),     // address of the function, nArgs
+    Return,
+}
+
+// Temp, pointer and static words have a fixed address
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+    Constant(u16),
+    Local(u16),
+    Argument(u16),
+    This(u16),
+    That(u16),
+    Fixed(u16),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operation {
+    Add,
+    Sub,
+    Neg,
+    Eq,
+    Gt,
+    Lt,
+    And,
+    Or,
+    Not,
+}
+
+impl Vm {
+    // Loads the commands of `parse_program`, where every jump and call has a target
+    fn new(commands: Vec<CommandType>) -> Self {
+        // First pass: addresses of the functions and labels
+        let mut functions = Vec::new();
+        let mut function_addresses = HashMap::new();
+        let mut labels = HashMap::new();
+        let mut file = String::new();
+        let mut address = 0;
+        for command in &commands {
+            match command {
+                CommandType::NewFile(name) => file = name.clone(),
+                CommandType::Label(function, label) => {
+                    labels.insert(format!("{}${}", function, label), address);
+                }
+                CommandType::Function(name, _) => {
+                    function_addresses.entry(name.clone()).or_insert(address);
+                    functions.push(Function { name: name.clone(), file: file.clone() });
+                    address += 1;
+                }
+                _ => address += 1,
+            }
+        }
+
+        let mut vm = Vm {
+            ram: vec![0; RAM_SIZE],
+            program: Vec::with_capacity(address),
+            commands: Vec::with_capacity(address),
+            functions,
+            statics: HashMap::new(),
+            calls: Vec::new(),
+            called: false,
+            pc: 0,
+            steps: 0,
+            halt: HaltCheck::default(),
+        };
+
+        // Second pass: instructions with resolved addresses
+        let mut function_count = 0;
+        for command in commands {
+            let instruction = match &command {
+                CommandType::NewFile(name) => {
+                    file = name.clone();
+                    continue;
+                }
+                CommandType::Label(_, _) => continue,
+                CommandType::Arithmetic(command) => Instruction::Arithmetic(operation(command)),
+                CommandType::Push(segment, index) => Instruction::Push(vm.operand(segment, *index, &file)),
+                CommandType::Pop(segment, index) => Instruction::Pop(vm.operand(segment, *index, &file)),
+                CommandType::Goto(function, label) | CommandType::If(function, label) => {
+                    let target = labels[&format!("{}${}", function, label)];
+                    match command {
+                        CommandType::Goto(..) => Instruction::Goto(target),
+                        _ => Instruction::IfGoto(target),
+                    }
+                }
+                CommandType::Function(_, nvars) => {
+                    function_count += 1;
+                    Instruction::Function(function_count - 1, *nvars)
+                }
+                CommandType::Call(name, nargs) => Instruction::Call(function_addresses[name], *nargs),
+                CommandType::Return => Instruction::Return,
+            };
+            vm.program.push(instruction);
+            vm.commands.push(command);
+        }
+        // Bootstrap: SP = 256, call Sys.init
+        if let Some(&address) = function_addresses.get("Sys.init") {
+            vm.ram[SP] = STACK;
+            vm.call(address, 0, vm.program.len());
+        }
+        vm
+    }
+
+    // Loads a `.vm` file or a folder of `.vm` files
+    pub fn load(input: &str) -> Result<Self, Vec<HVMError>> {
+        Self::load_with_library(input, &[])
+    }
+
+    // Loads like `load`, with the VM classes of `library` (file name without extension, VM code)
+    pub fn load_with_library(input: &str, library: &[(String, String)]) -> Result<Self, Vec<HVMError>> {
+        let files = source_files(input).map_err(|e| vec![HVMError::Io(input.to_string(), e)])?;
+        Ok(Self::new(parse_program(files, library)?))
+    }
+
+    // Resolves the temp, pointer and static words, statics get the next free address
+    fn operand(&mut self, segment: &MemorySegment, index: u16, file: &str) -> Operand {
+        match segment {
+            MemorySegment::Constant => Operand::Constant(index),
+            MemorySegment::Local => Operand::Local(index),
+            MemorySegment::Argument => Operand::Argument(index),
+            MemorySegment::This => Operand::This(index),
+            MemorySegment::That => Operand::That(index),
+            MemorySegment::Temp => Operand::Fixed(TEMP + index),
+            MemorySegment::Pointer => Operand::Fixed(THIS as u16 + index),
+            MemorySegment::Static => {
+                let next = STATIC + self.statics.len() as u16;
+                Operand::Fixed(*self.statics.entry((file.to_string(), index)).or_insert(next))
+            }
+        }
+    }
+
+    // Executes one command
+    pub fn step(&mut self) {
+        let instruction = match self.program.get(self.pc) {
+            Some(&instruction) => instruction,
+            None => return, // out of the program: nothing left to run
+        };
+        self.pc += 1;
+        self.steps += 1;
+
+        match instruction {
+            Instruction::Push(operand) => {
+                let value = match operand {
+                    Operand::Constant(value) => value,
+                    operand => self.read(self.address(operand)),
+                };
+                self.push(value);
+            }
+            Instruction::Pop(operand) => {
+                let value = self.pop();
+                let address = self.address(operand);
+                self.write(address, value);
+            }
+            Instruction::Arithmetic(operation) => self.arithmetic(operation),
+            Instruction::Goto(target) => self.jump(target),
+            Instruction::IfGoto(target) => {
+                if self.pop() != 0 {
+                    self.jump(target);
+                }
+            }
+            Instruction::Function(id, nvars) => {
+                // Entered by a call, or by running into it from the previous function
+                match self.calls.last_mut() {
+                    Some(current) if !self.called => *current = id,
+                    _ => self.calls.push(id),
+                }
+                self.called = false;
+                for _ in 0..nvars {
+                    self.push(0);
+                }
+            }
+            Instruction::Call(address, nargs) => self.call(address, nargs, self.pc),
+            Instruction::Return => self.r#return(),
+        }
+    }
+
+    // Runs `steps` commands, stops early if the program halts.
+    // Returns the number of commands executed.
+    pub fn run(&mut self, steps: u64) -> u64 {
+        let start = self.steps;
+        for _ in 0..steps {
+            if self.is_halted() {
+                break;
+            }
+            self.step();
+        }
+        self.steps - start
+    }
+
+    // Runs until the program halts, gives up after `max_steps` commands
+    pub fn run_until_halt(&mut self, max_steps: u64) -> Result<u64, HVMError> {
+        let executed = self.run(max_steps);
+        if self.is_halted() {
+            Ok(executed)
+        } else {
+            Err(HVMError::Timeout(max_steps))
+        }
+    }
+
+    // A program halts when it runs out of commands, returns from its first function
+    // or loops forever without reading the keyboard, like `while (true) {}` in `Sys.halt`
+    pub fn is_halted(&self) -> bool {
+        self.halt.halted || self.pc >= self.program.len()
+    }
+
+    fn jump(&mut self, target: usize) {
+        if target < self.pc {
+            self.halt.backward_jump((target, self.calls.len()), &self.ram);
+        }
+        self.pc = target;
+    }
+
+    // push returnAddress, LCL, ARG, THIS, THAT; ARG = SP - nArgs - 5; LCL = SP; goto function
+    fn call(&mut self, address: usize, nargs: u8, return_address: usize) {
+        let sp = self.ram[SP];
+        self.push(return_address as u16);
+        for register in [LCL, ARG, THIS, THAT] {
+            self.push(self.ram[register]);
+        }
+        self.write(ARG, sp.wrapping_sub(nargs as u16));
+        self.write(LCL, self.ram[SP]);
+        self.pc = address;
+        self.called = true;
+    }
+
+    // Restores the frame of the caller, the returned value replaces the arguments
+    fn r#return(&mut self) {
+        let frame = self.ram[LCL];
+        let word = |vm: &Self, offset: u16| vm.ram[frame.wrapping_sub(offset) as usize % RAM_SIZE];
+        let return_address = word(self, 5);
+
+        let value = self.pop();
+        let arg = self.ram[ARG];
+        self.write(arg as usize % RAM_SIZE, value);
+        self.write(SP, arg.wrapping_add(1));
+        self.write(THAT, word(self, 1));
+        self.write(THIS, word(self, 2));
+        self.write(ARG, word(self, 3));
+        self.write(LCL, word(self, 4));
+
+        // Without a caller, like a function started by a test script, the program ends
+        self.calls.pop();
+        self.pc = if self.calls.is_empty() { self.program.len() } else { return_address as usize };
+    }
+
+    fn arithmetic(&mut self, operation: Operation) {
+        let y = match operation {
+            Operation::Neg | Operation::Not => 0,
+            _ => self.pop(),
+        };
+        let top = (self.ram[SP].wrapping_sub(1) as usize) % RAM_SIZE;
+        let x = self.ram[top];
+        let boolean = |b: bool| if b { u16::MAX } else { 0 };
+        let value = match operation {
+            Operation::Add => x.wrapping_add(y),
+            Operation::Sub => x.wrapping_sub(y),
+            Operation::Neg => x.wrapping_neg(),
+            Operation::Eq => boolean(x == y),
+            Operation::Gt => boolean((x as i16) > (y as i16)),
+            Operation::Lt => boolean((x as i16) < (y as i16)),
+            Operation::And => x & y,
+            Operation::Or => x | y,
+            Operation::Not => !x,
+        };
+        self.write(top, value);
+    }
+
+    fn push(&mut self, value: u16) {
+        let sp = self.ram[SP];
+        self.write(sp as usize % RAM_SIZE, value);
+        self.write(SP, sp.wrapping_add(1));
+    }
+
+    fn pop(&mut self) -> u16 {
+        let sp = self.ram[SP].wrapping_sub(1);
+        self.write(SP, sp);
+        self.read(sp as usize % RAM_SIZE)
+    }
+
+    // Word read by the program
+    fn read(&mut self, address: usize) -> u16 {
+        if address == KBD {
+            self.halt.reset();
+        }
+        self.ram[address]
+    }
+
+    // Word written by the program
+    fn write(&mut self, address: usize, value: u16) {
+        self.halt.write(address, self.ram[address]);
+        self.ram[address] = value;
+    }
+
+    // RAM address of a word of a segment
+    fn address(&self, operand: Operand) -> usize {
+        let (base, index) = match operand {
+            Operand::Local(index) => (self.ram[LCL], index),
+            Operand::Argument(index) => (self.ram[ARG], index),
+            Operand::This(index) => (self.ram[THIS], index),
+            Operand::That(index) => (self.ram[THAT], index),
+            Operand::Fixed(address) => (0, address),
+            Operand::Constant(_) => unreachable!("constants have no address"),
+        };
+        base.wrapping_add(index) as usize % RAM_SIZE
+    }
+
+    // Value of `push segment index` in the current function, None for statics of other files
+    pub fn segment(&self, segment: &MemorySegment, index: u16) -> Option<u16> {
+        let operand = match segment {
+            MemorySegment::Constant => return Some(index),
+            MemorySegment::Static => {
+                let file = &self.functions[*self.calls.last()?].file;
+                Operand::Fixed(*self.statics.get(&(file.clone(), index))?)
+            }
+            MemorySegment::Local => Operand::Local(index),
+            MemorySegment::Argument => Operand::Argument(index),
+            MemorySegment::This => Operand::This(index),
+            MemorySegment::That => Operand::That(index),
+            MemorySegment::Temp => Operand::Fixed(TEMP + index),
+            MemorySegment::Pointer => Operand::Fixed(THIS as u16 + index),
+        };
+        Some(self.ram[self.address(operand)])
+    }
+
+    // Address of the static variable `index` of `file` (name without extension)
+    pub fn static_address(&self, file: &str, index: u16) -> Option<u16> {
+        self.statics.get(&(file.to_string(), index)).copied()
+    }
+
+    // Words pushed on the stack, the top last
+    pub fn stack(&self) -> &[u16] {
+        let sp = (self.ram[SP] as usize).min(RAM_SIZE);
+        &self.ram[(STACK as usize).min(sp)..sp]
+    }
+
+    // Functions being run, from the first one to the current one
+    pub fn call_stack(&self) -> Vec<&str> {
+        self.calls.iter().map(|&id| self.functions[id].name.as_str()).collect()
+    }
+
+    pub fn current_function(&self) -> Option<&str> {
+        self.calls.last().map(|&id| self.functions[id].name.as_str())
+    }
+
+    // Next command to run
+    pub fn current_command(&self) -> Option<&CommandType> {
+        self.commands.get(self.pc)
+    }
+
+    pub fn ram(&self, address: usize) -> u16 {
+        self.ram[address % RAM_SIZE]
+    }
+
+    pub fn set_ram(&mut self, address: usize, value: u16) {
+        self.ram[address % RAM_SIZE] = value;
+        self.halt.reset();
+    }
+
+    // The whole RAM, SCREEN and KBD included
+    pub fn memory(&self) -> &[u16] {
+        &self.ram
+    }
+
+    // Number of the next command, labels excluded
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+}
+
+fn operation(command: &str) -> Operation {
+    match command {
+        "add" => Operation::Add,
+        "sub" => Operation::Sub,
+        "neg" => Operation::Neg,
+        "eq" => Operation::Eq,
+        "gt" => Operation::Gt,
+        "lt" => Operation::Lt,
+        "and" => Operation::And,
+        "or" => Operation::Or,
+        "not" => Operation::Not,
+        _ => unreachable!("unknown arithmetic commands are rejected by the parser"),
+    }
+}
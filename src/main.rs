@@ -1,21 +1,293 @@
-// use std::env;
-// use std::process;
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::process;
 
-use hack_assembler::assemble;
+use hack_assembler::{assemble_with_options, disassemble, Options};
+use hack_emulator::{run_test_script, run_with_keyboard, save_screen, Cpu, KeyboardScript, ROM_SIZE};
+
+const USAGE: &str = "Usage: n2t <command> <input> [options]
+
+Commands:
+  asm <file.asm>           Assembles into <file.hack>
+      -o <file.hack>       Output file
+      --listing <file>     Writes a listing (ROM address, binary, source line)
+      --symbols <file>     Writes the symbol map (labels and variables)
+  disasm <file.hack>       Disassembles into <file.dis.asm>
+      -o <file.asm>        Output file
+  vm <file.vm|dir>         Translates VM code into <name>.asm
+      -o <file.asm>        Output file
+      --compact            Shares one call, return and comparison routine (smaller ROM)
+      -O                   Optimizes common pairs of VM commands
+      --rom-usage          Prints the ROM used with and without the shared routines
+  jack <file.jack|dir>     Compiles Jack classes next to their sources
+      --annotate           Annotates identifiers of the XML output (category, index, usage)
+      --whole-program      Checks calls between the classes of the folder and to the OS
+  build <dir>              Compiles a Jack program down to <dir>/<dir>.hack
+                           (the OS classes it uses come with it)
+      -o <file.hack>       Output file
+      --compact            Shares one call, return and comparison routine (smaller ROM)
+      -O                   Optimizes common pairs of VM commands
+      --rom-usage          Prints the ROM used with and without the shared routines
+  run <file.hack|file.asm|dir>
+                           Runs a program on the Hack CPU (a folder is built first)
+      --compact, -O        Builds a folder with the shared routines, optimized
+      --cycles <n>         Number of instructions to run (default: until it halts)
+      --ram <from>..<to>   RAM words to print once stopped (default: 0..16)
+      --screen <file>      Saves the screen once stopped (.png or .ppm)
+      --keys <file>        Replays a keyboard script into KBD
+  test <file.tst>          Runs a CPU or VM emulator test script";
+
+// Options that take a value
+const VALUE_OPTIONS: [&str; 7] = ["-o", "--listing", "--symbols", "--cycles", "--ram", "--screen", "--keys"];
+
+// Options without value
+const FLAG_OPTIONS: [&str; 5] = ["--annotate", "--whole-program", "--compact", "-O", "--rom-usage"];
+
+// Cycles run by `run` before giving up on a program that doesn't halt
+const MAX_CYCLES: u64 = 100_000_000;
+
+struct Args {
+    command: String,
+    input: String,
+    options: Vec<(String, String)>,
+}
+
+impl Args {
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.option(name).is_some()
+    }
+}
 
 fn main() {
-    // $ HackAssembler Add.asm
-    // let args: Vec<String> = env::args().collect();
-    // Check filename is provided
-    // if args.len() < 2 {
-    //     eprintln!("No files provided!");
-    //     process::exit(1);
-    // }
-    // Filename
-    // let filename = &args[1];
-    // Create a HackAssembler
-    let input = "../assembler/tests/max/Max.asm";
-    let output = "../assembler/tests/max/Test.hack";
-    let _ = assemble(input, output);
-    // assembler.execute();
+    // $ n2t asm Add.asm -o Add.hack
+    let args = match parse_args(env::args().skip(1).collect()) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("Error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let result = match args.command.as_str() {
+        "asm" => asm(&args),
+        "disasm" => disasm(&args),
+        "vm" => vm(&args),
+        "jack" => jack(&args),
+        "build" => build(&args).map(|_| ()),
+        "run" => run(&args),
+        "test" => test(&args),
+        command => {
+            eprintln!("Error: unknown command `{}`\n\n{}", command, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(errors) = result {
+        for error in errors {
+            eprintln!("{}", error);
+        }
+        process::exit(1);
+    }
+}
+
+fn parse_args(args: Vec<String>) -> Result<Args, String> {
+    let mut args = args.into_iter();
+    let command = args.next().ok_or("no command provided")?;
+    let mut input = None;
+    let mut options = Vec::new();
+
+    while let Some(arg) = args.next() {
+        if VALUE_OPTIONS.contains(&arg.as_str()) {
+            let value = args.next().ok_or(format!("`{}` needs a value", arg))?;
+            options.push((arg, value));
+        } else if FLAG_OPTIONS.contains(&arg.as_str()) {
+            options.push((arg, String::new()));
+        } else if arg.starts_with('-') {
+            return Err(format!("unknown option `{}`", arg));
+        } else if input.is_none() {
+            input = Some(arg);
+        } else {
+            return Err(format!("unexpected argument `{}`", arg));
+        }
+    }
+
+    let input = input.ok_or("no input provided")?;
+    Ok(Args { command, input, options })
+}
+
+// Formats every error to be printed by `main`
+fn errors<E: Display>(errors: Vec<E>) -> Vec<String> {
+    errors.iter().map(|e| e.to_string()).collect()
+}
+
+fn error<E: Display>(error: E) -> Vec<String> {
+    vec![error.to_string()]
+}
+
+// `<input>` with another extension
+fn with_extension(input: &str, extension: &str) -> String {
+    Path::new(input).with_extension(extension).to_string_lossy().into_owned()
+}
+
+fn asm(args: &Args) -> Result<(), Vec<String>> {
+    let output = args.option("-o").map_or_else(|| with_extension(&args.input, "hack"), str::to_string);
+    let options = Options {
+        listing: args.option("--listing").map(str::to_string),
+        symbols: args.option("--symbols").map(str::to_string),
+    };
+    assemble_with_options(&args.input, &output, &options).map_err(errors)
+}
+
+fn disasm(args: &Args) -> Result<(), Vec<String>> {
+    // Don't overwrite the original source next to the binary
+    let output = args.option("-o").map_or_else(|| with_extension(&args.input, "dis.asm"), str::to_string);
+    disassemble(&args.input, &output).map_err(errors)
+}
+
+fn vm(args: &Args) -> Result<(), Vec<String>> {
+    let options = hvm::Options {
+        compact: args.flag("--compact"),
+        optimize: args.flag("-O"),
+        rom_usage: args.flag("--rom-usage"),
+    };
+    let usage = hvm::translate_with_options(&args.input, args.option("-o"), &[], &options).map_err(errors)?;
+    if let Some(usage) = usage {
+        print_rom_usage(usage);
+    }
+    Ok(())
+}
+
+// ROM used with and without the shared routines
+fn print_rom_usage(usage: hvm::RomUsage) {
+    let saved = usage.inline as f64 - usage.compact as f64;
+    println!(
+        "ROM: {} instructions inline, {} with shared routines ({:.0}% smaller, the ROM holds {})",
+        usage.inline,
+        usage.compact,
+        100.0 * saved / usage.inline as f64,
+        ROM_SIZE
+    );
+}
+
+fn jack(args: &Args) -> Result<(), Vec<String>> {
+    let options = compiler::Options {
+        annotate_identifiers: args.flag("--annotate"),
+        whole_program: args.flag("--whole-program"),
+    };
+    compiler::analyze_with_options(&args.input, &options).map_err(errors)
+}
+
+// Jack folder -> VM files -> <dir>.asm -> <dir>.hack, returns the `.hack` path
+fn build(args: &Args) -> Result<String, Vec<String>> {
+    let dir = Path::new(&args.input);
+    if !dir.is_dir() {
+        return Err(error(format!("{}: expected a folder of .jack files", args.input)));
+    }
+    // `.` and `..` have no name of their own, the program is named after the full path
+    let dir = fs::canonicalize(dir).map_err(|e| error(format!("{}: {}", args.input, e)))?;
+    let Some(name) = dir.file_name().and_then(|n| n.to_str()) else {
+        return Err(error(format!("{}: the program is named after the folder, which has no name", args.input)));
+    };
+    let asm = dir.join(format!("{}.asm", name)).to_string_lossy().into_owned();
+    let hack = args.option("-o").map_or_else(|| with_extension(&asm, "hack"), str::to_string);
+
+    // A build is a whole program
+    let options = compiler::Options {
+        whole_program: true,
+        ..Default::default()
+    };
+    compiler::analyze_with_options(&args.input, &options).map_err(errors)?;
+    // The OS classes the program needs and doesn't define
+    let os = compiler::link_os(&args.input).map_err(errors)?;
+    let vm_options = hvm::Options {
+        compact: args.flag("--compact"),
+        optimize: args.flag("-O"),
+        rom_usage: args.flag("--rom-usage"),
+    };
+    let usage = hvm::translate_with_options(&args.input, Some(&asm), &os, &vm_options).map_err(errors)?;
+    if let Some(usage) = usage {
+        print_rom_usage(usage);
+    }
+    assemble_with_options(&asm, &hack, &Options::default()).map_err(errors)?;
+
+    Ok(hack)
+}
+
+fn run(args: &Args) -> Result<(), Vec<String>> {
+    let program = if Path::new(&args.input).is_dir() {
+        build(&Args {
+            command: args.command.clone(),
+            input: args.input.clone(),
+            options: args.options.iter().filter(|(name, _)| name == "--compact" || name == "-O").cloned().collect(),
+        })?
+    } else {
+        args.input.clone()
+    };
+    let mut cpu = Cpu::load(&program).map_err(error)?;
+
+    let cycles = match args.option("--cycles") {
+        Some(cycles) => {
+            let cycles = cycles.parse::<u64>();
+            Some(cycles.map_err(|_| error("invalid cycle count, expected a number"))?)
+        }
+        None => None,
+    };
+    let keys = match args.option("--keys") {
+        Some(path) => KeyboardScript::load(path).map_err(error)?,
+        None => KeyboardScript::default(),
+    };
+
+    match cycles {
+        Some(cycles) => {
+            run_with_keyboard(&mut cpu, &keys, cycles);
+        }
+        None => {
+            run_with_keyboard(&mut cpu, &keys, MAX_CYCLES);
+            if !cpu.is_halted() {
+                return Err(error(format!("{}: program did not halt after {} cycles", program, MAX_CYCLES)));
+            }
+        }
+    }
+
+    let state = if cpu.is_halted() { "halted" } else { "stopped" };
+    println!("{} after {} cycles (PC={})", state, cpu.cycles(), cpu.pc());
+
+    let (from, to) = parse_range(args.option("--ram").unwrap_or("0..16")).map_err(error)?;
+    for address in from..to {
+        println!("RAM[{}] = {}", address, cpu.ram(address) as i16);
+    }
+
+    if let Some(screen) = args.option("--screen") {
+        save_screen(&cpu, screen).map_err(error)?;
+    }
+    Ok(())
+}
+
+fn test(args: &Args) -> Result<(), Vec<String>> {
+    let outcome = run_test_script(&args.input).map_err(error)?;
+    match outcome.failure {
+        None => {
+            println!("{}: passed", args.input);
+            Ok(())
+        }
+        Some(mismatch) => Err(error(format!(
+            "{}: comparison failure at line {}\n  expected: {}\n  found:    {}",
+            args.input, mismatch.line, mismatch.expected, mismatch.found
+        ))),
+    }
+}
+
+// Parses `from..to`
+fn parse_range(range: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("invalid RAM range `{}`, expected <from>..<to>", range);
+    let (from, to) = range.split_once("..").ok_or_else(invalid)?;
+    let from = from.parse::<usize>().map_err(|_| invalid())?;
+    let to = to.parse::<usize>().map_err(|_| invalid())?;
+    Ok((from, to))
 }
@@ -0,0 +1,89 @@
+// Parsing rules of the assembler: comments after an instruction and symbols starting with R,
+// errors with their location, listing and symbol map
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::Project;
+use hack_assembler::{assemble, assemble_str, assemble_with_options, AsmError, Options};
+
+#[test]
+fn comments_after_instructions_are_ignored() {
+    let commented = assemble_str("(START) // entry\n@2 // two\nD=A // D = 2\n@START // loop\n0;JMP // forever\n");
+    let plain = assemble_str("(START)\n@2\nD=A\n@START\n0;JMP\n");
+    assert_eq!(commented.unwrap(), plain.unwrap());
+}
+
+#[test]
+fn symbols_starting_with_r_are_kept_whole() {
+    let words = assemble_str("@R0\n@R5\n@R15\n@RET\n@RUNNING\n(RET)\n@RUNNING\n").unwrap();
+    // R0-R15 are predefined, RET is a label (ROM 5) and RUNNING the first variable (RAM 16)
+    assert_eq!(words, [0, 5, 15, 5, 16, 16]);
+}
+
+#[test]
+fn every_error_is_reported_with_its_file_and_line() {
+    let source = "// broken\n@40000\nD=D+X\n  AMX=M;JMPP\n(LOOP)\n@LOOP\n0;JMP\n@-1\n";
+    let project = Project::new("errors", &[("Prog.asm", source)]);
+    let (input, output) = (project.file("Prog.asm"), project.file("Prog.hack"));
+
+    let errors: Vec<String> = assemble(&input, &output).unwrap_err().iter().map(AsmError::to_string).collect();
+    let expected = [
+        "2:2: invalid address `40000` (expected a value between 0 and 32767)",
+        "3:3: unknown comp instruction `D+X`",
+        "4:3: unknown destination `AMX`",
+        "4:9: unknown jump `JMPP`",
+        "8:2: invalid address `-1` (expected a value between 0 and 32767)",
+    ];
+    let expected: Vec<String> = expected.iter().map(|message| format!("{}:{}", input, message)).collect();
+    assert_eq!(errors, expected);
+    assert!(!Path::new(&output).exists());
+}
+
+const COUNTER_ASM: &str = "// Adds 1 to sum forever
+@sum
+M=0
+(LOOP)
+@sum
+M=M+1 // increment
+@LOOP
+0;JMP
+@i
+";
+
+#[test]
+fn listing_has_the_address_word_and_line_of_each_instruction() {
+    let project = Project::new("listing", &[("Counter.asm", COUNTER_ASM)]);
+    let options = Options { listing: Some(project.file("Counter.lst")), symbols: None };
+    assemble_with_options(&project.file("Counter.asm"), &project.file("Counter.hack"), &options).unwrap();
+
+    let listing = fs::read_to_string(project.file("Counter.lst")).unwrap();
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "    0  0000000000010000      2: @sum",
+            "    1  1110101010001000      3: M=0",
+            "                             4: (LOOP)",
+            "    2  0000000000010000      5: @sum",
+            "    3  1111110111001000      6: M=M+1 // increment",
+            "    4  0000000000000010      7: @LOOP",
+            "    5  1110101010000111      8: 0;JMP",
+            "    6  0000000000010001      9: @i",
+        ]
+    );
+    assert!(!Path::new(&project.file("Counter.sym")).exists());
+}
+
+#[test]
+fn symbol_map_has_the_labels_then_the_variables() {
+    let project = Project::new("symbols", &[("Counter.asm", &format!("{}(END)\n@END\n0;JMP\n@R13\n", COUNTER_ASM))]);
+    let options = Options { listing: None, symbols: Some(project.file("Counter.sym")) };
+    assemble_with_options(&project.file("Counter.asm"), &project.file("Counter.hack"), &options).unwrap();
+
+    // Predefined symbols are not listed
+    let symbols = fs::read_to_string(project.file("Counter.sym")).unwrap();
+    assert_eq!(symbols, "// Labels (ROM address)\nLOOP 2\nEND 7\n// Variables (RAM address)\nsum 16\ni 17\n");
+}
@@ -0,0 +1,59 @@
+// Temporary folders of the integration tests
+
+// Each test file uses part of the helpers
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Folders created so far by the test binary
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+// Folder `name` of a test, alone in a temporary folder removed when dropped.
+// The folder keeps its name: the translator names the `.asm` after it.
+pub struct Project(PathBuf);
+
+impl Project {
+    // Folder with the given files
+    pub fn new(name: &str, files: &[(&str, &str)]) -> Self {
+        let project = Self::empty(name);
+        for (file, source) in files {
+            fs::write(project.0.join(file), source).unwrap();
+        }
+        project
+    }
+
+    // Copy of `tests/vm/<name>`
+    pub fn fixture(name: &str) -> Self {
+        let project = Self::empty(name);
+        let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/vm").join(name);
+        for entry in fs::read_dir(source).unwrap() {
+            let path = entry.unwrap().path();
+            fs::copy(&path, project.0.join(path.file_name().unwrap())).unwrap();
+        }
+        project
+    }
+
+    fn empty(name: &str) -> Self {
+        let count = COUNT.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("n2t_{}_{}", std::process::id(), count)).join(name);
+        fs::create_dir_all(&dir).unwrap();
+        Project(dir)
+    }
+
+    pub fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+
+    // Path of `file` in the folder
+    pub fn file(&self, file: &str) -> String {
+        self.0.join(file).to_str().unwrap().to_string()
+    }
+}
+
+impl Drop for Project {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(self.0.parent().unwrap());
+    }
+}
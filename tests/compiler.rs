@@ -0,0 +1,176 @@
+// Tokens of the Jack tokenizer, and errors of the compiler: lexical errors,
+// syntax errors after recovery and the source line with a caret under the error
+
+mod common;
+
+use common::Project;
+use compiler::tokenizer::{Keyword, Token};
+use compiler::{Diagnostic, JackError};
+
+// `line:column: message` of the lexical errors of `source`
+fn lexer_errors(source: &str) -> Vec<String> {
+    let diagnostics = compiler::tokenize_source(source).unwrap_err();
+    diagnostics.iter().map(|Diagnostic { span, message }| format!("{}:{}: {}", span.line, span.column, message)).collect()
+}
+
+// First line of the errors of the folder with `Main.jack`, without the file name
+fn compile_errors(name: &str, source: &str) -> Vec<String> {
+    let project = Project::new(name, &[("Main.jack", source)]);
+    let errors = compiler::analyze(project.path()).unwrap_err();
+    let file = project.file("Main.jack");
+    errors
+        .iter()
+        .map(|error| {
+            let text = error.to_string();
+            let first_line = text.lines().next().unwrap();
+            first_line.strip_prefix(&format!("{}:", file)).unwrap_or(first_line).to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn tokens_have_a_value_and_a_span() {
+    let source = "/** doc */ let s=\"a < b\";\n  /* a\n b */do Foo.bar(32767); // end\n";
+    let tokens = compiler::tokenize_source(source).unwrap();
+    let found: Vec<(Token, usize, usize, usize)> =
+        tokens.into_iter().map(|t| (t.token, t.span.line, t.span.column, t.span.length)).collect();
+    let expected = [
+        (Token::Keyword(Keyword::Let), 1, 12, 3),
+        (Token::Identifier("s".to_string()), 1, 16, 1),
+        (Token::Symbol('='), 1, 17, 1),
+        (Token::StringConst("a < b".to_string()), 1, 18, 7),
+        (Token::Symbol(';'), 1, 25, 1),
+        (Token::Keyword(Keyword::Do), 3, 6, 2),
+        (Token::Identifier("Foo".to_string()), 3, 9, 3),
+        (Token::Symbol('.'), 3, 12, 1),
+        (Token::Identifier("bar".to_string()), 3, 13, 3),
+        (Token::Symbol('('), 3, 16, 1),
+        (Token::IntConst(32767), 3, 17, 5),
+        (Token::Symbol(')'), 3, 22, 1),
+        (Token::Symbol(';'), 3, 23, 1),
+    ];
+    assert_eq!(found, expected);
+
+    let tokens = compiler::tokenize_source("if (x<1) {return \"&\";}").unwrap();
+    let xml = compiler::tokens_to_xml(&tokens);
+    let lines: Vec<&str> = xml.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "<tokens>",
+            "<keyword> if </keyword>",
+            "<symbol> ( </symbol>",
+            "<identifier> x </identifier>",
+            "<symbol> &lt; </symbol>",
+            "<integerConstant> 1 </integerConstant>",
+            "<symbol> ) </symbol>",
+            "<symbol> { </symbol>",
+            "<keyword> return </keyword>",
+            "<stringConstant> &amp; </stringConstant>",
+            "<symbol> ; </symbol>",
+            "<symbol> } </symbol>",
+            "</tokens>",
+        ]
+    );
+}
+
+#[test]
+fn lexical_errors() {
+    let source = "class Main {\n  field int x # y;\n  static String s;\n  let x = 40000;\n  let s = \"abc\n  /* never closed\n}";
+    assert_eq!(
+        lexer_errors(source),
+        [
+            "2:15: illegal character `#`",
+            "4:11: integer constant `40000` is out of range (0..32767)",
+            "5:11: unterminated string constant, `\"` is missing",
+            "6:3: unterminated comment, `*/` is missing",
+        ]
+    );
+}
+
+#[test]
+fn parsing_goes_on_after_lexical_errors() {
+    let source = "
+class Main {
+    function void main() {
+        var int x;
+        let x = 40000;
+        let x = 1 $ 2;
+        let x = ;
+        do Output.printInt(x);
+        return;
+    }
+}
+";
+    assert_eq!(
+        compile_errors("lexer", source),
+        [
+            "5:17: integer constant `40000` is out of range (0..32767)",
+            "6:19: illegal character `$`",
+            "6:21: expected `;` but found `2`",
+            "7:17: expected expression but found `;`",
+        ]
+    );
+}
+
+#[test]
+fn every_broken_statement_and_declaration_is_reported() {
+    let source = "
+class Main {
+    field int;
+    static boolean done;
+
+    function void main() {
+        var int x, y;
+        let x = (1 + ;
+        if (x) { let y = 2 } else { let y = 3; }
+        while (x > 0) { let x = x - 1; }
+        do Output.printInt(y;
+        let y = 3 +
+        return;
+    }
+
+    method int broken( {
+        return 0;
+    }
+
+    function int ok() {
+        return 1;
+    }
+}
+";
+    assert_eq!(
+        compile_errors("recovery", source),
+        [
+            "3:14: expected identifier but found `;`",
+            "8:22: expected expression but found `;`",
+            "9:28: expected `;` but found `}`",
+            "11:29: expected `)` but found `;`",
+            "13:9: expected expression but found `return`",
+            "16:24: expected type but found `{`",
+        ]
+    );
+}
+
+#[test]
+fn caret_marks_the_column_of_the_error() {
+    let source = "class Main {\n    function void main() {\n\tvar int x;\n\tlet x = 3 +;\n        return;\n    }\n}\n";
+    let project = Project::new("caret", &[("Main.jack", source)]);
+    let errors: Vec<JackError> = compiler::analyze(project.path()).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    // The tab counts as one column
+    let expected = format!(
+        "{}:4:13: expected expression but found `;`\n 4 |  let x = 3 +;\n   |             ^",
+        project.file("Main.jack")
+    );
+    assert_eq!(errors[0].to_string(), expected);
+
+    let source = "class Main {\n  function void main() {\n    do Output.printString(\"never closed);\n    return;\n  }\n}\n";
+    let project = Project::new("carets", &[("Main.jack", source)]);
+    let errors = compiler::analyze(project.path()).unwrap_err();
+    let expected = format!(
+        "{}:3:27: unterminated string constant, `\"` is missing\n 3 |     do Output.printString(\"never closed);\n   |                           ^^^^^^^^^^^^^^^",
+        project.file("Main.jack")
+    );
+    assert_eq!(errors[0].to_string(), expected);
+}
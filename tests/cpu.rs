@@ -0,0 +1,75 @@
+// The Hack ALU: every comp code of the C-instruction, with D = x and A or M = y
+
+use hack_assembler::assemble_str;
+use hack_emulator::Cpu;
+
+// Runs `D=<comp>` with D = x and A = y, or A = 100 and RAM[100] = y for the M forms
+fn compute(comp: &str, x: i16, y: i16) -> i16 {
+    let rom = assemble_str(&format!("D={}\n", comp)).unwrap();
+    let mut cpu = Cpu::new(rom).unwrap();
+    cpu.set_d(x as u16);
+    if comp.contains('M') {
+        cpu.set_a(100);
+        cpu.set_ram(100, y as u16);
+    } else {
+        cpu.set_a(y as u16);
+    }
+    cpu.step();
+    cpu.d() as i16
+}
+
+// Comp code and its result for x and y
+type Comp = (&'static str, fn(i16, i16) -> i16);
+
+const COMPS: [Comp; 18] = [
+    ("0", |_, _| 0),
+    ("1", |_, _| 1),
+    ("-1", |_, _| -1),
+    ("D", |x, _| x),
+    ("A", |_, y| y),
+    ("!D", |x, _| !x),
+    ("!A", |_, y| !y),
+    ("-D", |x, _| x.wrapping_neg()),
+    ("-A", |_, y| y.wrapping_neg()),
+    ("D+1", |x, _| x.wrapping_add(1)),
+    ("A+1", |_, y| y.wrapping_add(1)),
+    ("D-1", |x, _| x.wrapping_sub(1)),
+    ("A-1", |_, y| y.wrapping_sub(1)),
+    ("D+A", |x, y| x.wrapping_add(y)),
+    ("D-A", |x, y| x.wrapping_sub(y)),
+    ("A-D", |x, y| y.wrapping_sub(x)),
+    ("D&A", |x, y| x & y),
+    ("D|A", |x, y| x | y),
+];
+
+// Pairs with signs, zero and both ends of the range, where + and - overflow
+const OPERANDS: [(i16, i16); 7] = [(0, 0), (5, 3), (3, 5), (-7, 12), (32767, 1), (-32768, -1), (0x5A5A, 0x0FF0)];
+
+#[test]
+fn every_comp_code_computes_its_function() {
+    for (comp, expected) in COMPS {
+        for (x, y) in OPERANDS {
+            assert_eq!(compute(comp, x, y), expected(x, y), "{} with x = {}, y = {}", comp, x, y);
+            // The a bit reads y from M instead of A
+            if comp.contains('A') {
+                let comp = comp.replace('A', "M");
+                assert_eq!(compute(&comp, x, y), expected(x, y), "{} with x = {}, y = {}", comp, x, y);
+            }
+        }
+    }
+}
+
+#[test]
+fn results_set_the_jump_conditions() {
+    // D;JLT, D;JEQ... jump to 10 when the comp result matches
+    let cases = [("JGT", [false, false, true]), ("JEQ", [false, true, false]), ("JLT", [true, false, false])];
+    for (jump, expected) in cases {
+        for (value, jumps) in [-3i16, 0, 3].into_iter().zip(expected) {
+            let rom = assemble_str(&format!("@10\nD;{}\n", jump)).unwrap();
+            let mut cpu = Cpu::new(rom).unwrap();
+            cpu.set_d(value as u16);
+            cpu.run(2);
+            assert_eq!(cpu.pc() == 10, jumps, "D = {}, {}", value, jump);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+// Disassembled programs reassemble to the same binary words
+
+mod common;
+
+use common::Project;
+use hack_assembler::{assemble_file, assemble_str, disassemble};
+
+const COMPS: [&str; 28] = [
+    "0", "1", "-1", "D", "A", "M", "!D", "!A", "!M", "-D", "-A", "-M", "D+1", "A+1", "M+1", "D-1", "A-1", "M-1",
+    "D+A", "D+M", "D-A", "D-M", "A-D", "M-D", "D&A", "D&M", "D|A", "D|M",
+];
+const DESTS: [&str; 8] = ["", "M=", "D=", "MD=", "A=", "AM=", "AD=", "AMD="];
+const JUMPS: [&str; 8] = ["", ";JGT", ";JEQ", ";JGE", ";JLT", ";JNE", ";JLE", ";JMP"];
+
+// Assembles `source`, disassembles the words and assembles the result again
+fn round_trip(name: &str, source: &str) -> (Vec<u16>, Vec<u16>) {
+    let words = assemble_str(source).unwrap();
+    let binary: String = words.iter().map(|word| format!("{:016b}\n", word)).collect();
+    let project = Project::new(name, &[("Prog.hack", &binary)]);
+
+    let asm = project.file("Prog.dis.asm");
+    disassemble(&project.file("Prog.hack"), &asm).unwrap();
+    let reassembled = assemble_file(&asm).unwrap();
+    (words, reassembled)
+}
+
+#[test]
+fn every_comp_dest_and_jump_reassembles_bit_identically() {
+    // Every comp with every dest, every comp with every jump
+    let mut source = String::from("(START)\n");
+    for comp in COMPS {
+        for dest in DESTS {
+            source.push_str(&format!("{}{}\n", dest, comp));
+        }
+        for jump in JUMPS {
+            source.push_str(&format!("@START\n{}{}\n", comp, jump));
+        }
+    }
+    source.push_str("@END\n0;JMP\n(END)\n");
+
+    let (words, reassembled) = round_trip("comp", &source);
+    assert_eq!(words.len(), COMPS.len() * (DESTS.len() + 2 * JUMPS.len()) + 2);
+    assert_eq!(reassembled, words);
+}
+
+#[test]
+fn predefined_symbols_labels_and_variables_reassemble_bit_identically() {
+    let source = "
+        @SP
+        M=M+1
+        @LCL
+        D=M
+        @ARG
+        @THIS
+        @THAT
+        @R0
+        @R13
+        @R15
+        @SCREEN
+        D=A
+        @KBD
+        D=M
+        @32767
+        @counter
+        M=0
+    (LOOP)
+        @counter
+        MD=M+1
+        @LOOP
+        D;JLT
+    (HALT)
+        @HALT
+        0;JMP
+    ";
+    let (words, reassembled) = round_trip("symbols", source);
+    assert_eq!(&words[..4], [0, 0xFDC8, 1, 0xFC10]);
+    assert_eq!(reassembled, words);
+}
@@ -0,0 +1,70 @@
+// The CPU and VM emulators stop on the endless loop that ends a program,
+// whatever its code, and not on a loop that still changes the RAM or reads the keyboard
+
+mod common;
+
+use common::Project;
+use hack_emulator::{Cpu, KBD};
+use hvm::{Options, Vm};
+
+const MAX_CYCLES: u64 = 1_000_000;
+
+// Counts to 1000 in a loop, waits for a key, then loops forever
+const SYS_JACK: &str = "
+class Sys {
+    static int count, key;
+
+    function void init() {
+        var Array keyboard;
+
+        while (count < 1000) {
+            let count = count + 1;
+        }
+        let keyboard = 24576;
+        while (keyboard[0] = 0) {}
+        let key = keyboard[0];
+        while (true) {}
+        return;
+    }
+}
+";
+
+#[test]
+fn cpu_halts_on_compiled_while_true() {
+    let project = Project::new("cpu", &[("Sys.jack", SYS_JACK)]);
+    compiler::analyze_with_options(project.path(), &Default::default()).unwrap();
+
+    for compact in [false, true] {
+        for optimize in [false, true] {
+            let options = Options { compact, optimize, ..Default::default() };
+            let asm = project.file(&format!("{}{}.asm", compact, optimize));
+            hvm::translate_with_options(project.path(), Some(&asm), &[], &options).unwrap();
+            let mut cpu = Cpu::load(&asm).unwrap();
+
+            // Waiting for a key is not a halt
+            cpu.run(MAX_CYCLES);
+            assert!(!cpu.is_halted(), "{:?}", options);
+            assert_eq!(cpu.ram(16), 1000, "{:?}", options);
+
+            cpu.set_ram(KBD, 65);
+            cpu.run_until_halt(MAX_CYCLES).unwrap();
+            assert_eq!(cpu.ram(17), 65, "{:?}", options);
+        }
+    }
+}
+
+#[test]
+fn vm_halts_on_compiled_while_true() {
+    let project = Project::new("vm", &[("Sys.jack", SYS_JACK)]);
+    compiler::analyze_with_options(project.path(), &Default::default()).unwrap();
+    let mut vm = Vm::load(project.path()).unwrap();
+
+    // Waiting for a key is not a halt
+    vm.run(MAX_CYCLES);
+    assert!(!vm.is_halted());
+    assert_eq!(vm.ram(16), 1000);
+
+    vm.set_ram(KBD, 65);
+    vm.run_until_halt(MAX_CYCLES).unwrap();
+    assert_eq!(vm.ram(17), 65);
+}
@@ -0,0 +1,101 @@
+// Keyboard scripts: `<cycle> <key>` lines, replayed into KBD when the CPU reaches the cycle
+
+use hack_assembler::assemble_str;
+use hack_emulator::{key_code, run_with_keyboard, Cpu, KeyboardScript};
+
+// Reads KBD into RAM[100], RAM[101]... KBD is read when the cycle counter is 5, 14, 23...
+const RECORDER: &str = "
+@100
+D=A
+@R0
+M=D
+(LOOP)
+@KBD
+D=M
+@R0
+A=M
+M=D
+@R0
+M=M+1
+@LOOP
+0;JMP
+";
+
+#[test]
+fn script_lines() {
+    let source = "
+// Pong
+100 LEFT
+50 a      // sorted by cycle
+100 #42
+200 NONE
+300 F12
+300 esc
+400  ~
+";
+    let script = KeyboardScript::parse(source, "pong.kbd").unwrap();
+    // Events of the same cycle keep the order of the file
+    assert_eq!(script.events(), [(50, 97), (100, 130), (100, 42), (200, 0), (300, 152), (300, 140), (400, 126)]);
+}
+
+#[test]
+fn key_codes() {
+    let keys = [
+        ("A", 65), ("z", 122), ("0", 48), (" ", 32), ("SPACE", 32), ("ENTER", 128), ("NEWLINE", 128),
+        ("BACKSPACE", 129), ("LEFT", 130), ("UP", 131), ("RIGHT", 132), ("down", 133), ("HOME", 134),
+        ("END", 135), ("PAGEUP", 136), ("PAGEDOWN", 137), ("INSERT", 138), ("DELETE", 139), ("ESC", 140),
+        ("F1", 141), ("f12", 152), ("#0", 0), ("#32767", 32767), ("#", 35), ("NONE", 0),
+    ];
+    for (key, code) in keys {
+        assert_eq!(key_code(key), Some(code), "{}", key);
+    }
+    for key in ["F0", "F13", "#x", "LEFTT", "é", ""] {
+        assert_eq!(key_code(key), None, "{}", key);
+    }
+}
+
+#[test]
+fn script_errors() {
+    let errors = [
+        ("10 a\n20\n", "keys.kbd:2: expected `<cycle> <key>`, found `20`"),
+        ("\n\n-5 a\n", "keys.kbd:3: invalid cycle `-5`"),
+        ("10 a // comment\nlater b\n", "keys.kbd:2: invalid cycle `later`"),
+        ("10 F13\n", "keys.kbd:1: unknown key `F13`"),
+    ];
+    for (source, message) in errors {
+        let error = KeyboardScript::parse(source, "keys.kbd").unwrap_err();
+        assert_eq!(error.to_string(), message);
+    }
+}
+
+#[test]
+fn keys_reach_kbd_at_their_cycle() {
+    let mut cpu = Cpu::new(assemble_str(RECORDER).unwrap()).unwrap();
+    // 14 is seen by the read at cycle 14, 24 and 33 by the next reads
+    let script = KeyboardScript::parse("14 a\n24 LEFT\n33 NONE\n", "keys.kbd").unwrap();
+
+    assert_eq!(run_with_keyboard(&mut cpu, &script, 50), 50);
+    let read: Vec<u16> = (100..106).map(|address| cpu.ram(address)).collect();
+    assert_eq!(read, [0, 97, 97, 130, 0, 0]);
+}
+
+#[test]
+fn replay_goes_on_across_runs() {
+    let mut cpu = Cpu::new(assemble_str(RECORDER).unwrap()).unwrap();
+    let script = KeyboardScript::parse("20 b\n", "keys.kbd").unwrap();
+
+    // The key is not pressed before its cycle
+    assert_eq!(run_with_keyboard(&mut cpu, &script, 20), 20);
+    assert_eq!(cpu.ram(24576), 0);
+    run_with_keyboard(&mut cpu, &script, 10);
+    assert_eq!(cpu.ram(24576), 98);
+    let read: Vec<u16> = (100..104).map(|address| cpu.ram(address)).collect();
+    assert_eq!(read, [0, 0, 98, 0]);
+}
+
+#[test]
+fn replay_stops_when_the_program_ends() {
+    let mut cpu = Cpu::new(assemble_str("@1\nD=A\n").unwrap()).unwrap();
+    let script = KeyboardScript::parse("10 a\n", "keys.kbd").unwrap();
+    assert_eq!(run_with_keyboard(&mut cpu, &script, 100), 2);
+}
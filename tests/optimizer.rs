@@ -0,0 +1,231 @@
+// The peephole optimizer must not change what a program does: the optimized and unoptimized
+// translations run to the same RAM on the Hack CPU, and to the same RAM as the VM emulator.
+
+mod common;
+
+use common::Project;
+use hack_emulator::Cpu;
+use hvm::{Options, Vm};
+
+const MAX_CYCLES: u64 = 500_000_000;
+
+// Exercises every fused pair: `push constant; add|sub`, `push; pop` on every segment,
+// `push; if-goto` and `not; if-goto` with values other than true and false
+const SYS_VM: &str = "
+function Sys.init 0
+push constant 3000
+pop pointer 0
+push constant 4000
+pop pointer 1
+push constant 11
+push constant 22
+call Main.run 2
+pop static 0
+label HALT
+goto HALT
+";
+
+const MAIN_VM: &str = "
+function Main.run 5
+push argument 1
+pop local 4
+push argument 0
+pop this 0
+push local 4
+pop that 5
+push that 5
+pop temp 7
+push temp 7
+pop static 3
+push static 3
+pop local 0
+push constant 100
+pop argument 1
+push this 0
+push constant 5
+add
+pop this 1
+push this 1
+push constant 20
+sub
+pop this 2
+push pointer 1
+pop this 3
+push constant 0
+pop local 1
+label LOOP
+push local 1
+push constant 1
+add
+pop local 1
+push local 1
+push constant 3
+eq
+not
+if-goto LOOP
+push constant 5
+not
+if-goto SKIP
+push constant 999
+pop this 4
+label SKIP
+push constant 1
+neg
+not
+if-goto BAD
+push constant 77
+pop this 5
+label BAD
+push local 1
+if-goto NONZERO
+push constant 888
+pop this 6
+label NONZERO
+push constant 0
+if-goto END
+push local 1
+pop this 7
+label END
+push local 0
+return
+";
+
+const MAIN_JACK: &str = "
+class Main {
+    static int total;
+
+    function void main() {
+        var Array a;
+        var int i;
+        var String s;
+        var boolean done;
+
+        let a = Array.new(10);
+        let i = 0;
+        while (i < 10) {
+            let a[i] = (i * i) - 3;
+            let i = i + 1;
+        }
+        let i = 9;
+        while (~done) {
+            let total = total + a[i];
+            let i = i - 1;
+            if (i < 0) {
+                let done = true;
+            }
+        }
+        let s = String.new(8);
+        do s.appendChar(72);
+        do s.setInt(total / 7);
+        do Output.printString(s);
+        do Output.printInt(Math.sqrt(total));
+        do Screen.drawLine(0, 100, 200, 150);
+        return;
+    }
+}
+";
+
+// Translates and runs the program until it halts
+fn run(project: &Project, library: &[(String, String)], options: &Options) -> Cpu {
+    let asm = project.file(&format!("{}{}.asm", options.compact, options.optimize));
+    hvm::translate_with_options(project.path(), Some(&asm), library, options).unwrap();
+    let mut cpu = Cpu::load(&asm).unwrap();
+    cpu.run_until_halt(MAX_CYCLES).unwrap();
+    cpu
+}
+
+// Pointers, temp, statics, heap, screen and keyboard. R13-R15 are the scratch registers
+// of the translation and the stack holds return addresses, which depend on the code.
+fn state(memory: impl Fn(usize) -> u16) -> Vec<(usize, u16)> {
+    (0..13).chain(16..256).chain(2048..24577).map(|address| (address, memory(address))).collect()
+}
+
+// `push source i; pop target j` for every segment, with indexes that take the short
+// and the long way to the address, and `push constant n; add|sub`
+fn pairs_vm() -> String {
+    let sources = ["constant", "local", "argument", "this", "that", "temp", "static"];
+    let targets = ["local", "argument", "this", "that", "temp", "static"];
+    let mut code = String::from("function Main.pairs 8\n");
+    for (n, target) in targets.iter().enumerate() {
+        for j in 0..8 {
+            code.push_str(&format!("push constant {}\npop {} {}\n", 100 * n + j, target, j));
+        }
+    }
+    for source in sources.iter().chain(&["pointer"]) {
+        for i in [0, 1, 3, 4, 7] {
+            let i = if *source == "pointer" { i % 2 } else { i };
+            for target in targets {
+                for j in [2, 6] {
+                    code.push_str(&format!("push {} {}\npop {} {}\n", source, i, target, j));
+                }
+            }
+        }
+    }
+    for n in [0, 1, 2, 77, 32767] {
+        code.push_str(&format!("push local {}\npush constant {}\nadd\npop local {}\n", n % 8, n, n % 8));
+        code.push_str(&format!("push this {}\npush constant {}\nsub\npop that {}\n", n % 8, n, n % 8));
+    }
+    code.push_str("push constant 0\nreturn\n");
+    code
+}
+
+// The same program with and without -O leaves the same RAM on the CPU
+fn assert_optimization_keeps_ram(project: &Project, library: &[(String, String)]) {
+    for compact in [false, true] {
+        let run_state = |optimize| {
+            let cpu = run(project, library, &Options { compact, optimize, ..Default::default() });
+            state(|address| cpu.ram(address))
+        };
+        let (plain, optimized) = (run_state(false), run_state(true));
+        let difference = plain.iter().zip(&optimized).find(|(p, o)| p != o);
+        assert_eq!(difference, None, "compact: {}", compact);
+    }
+}
+
+fn assert_same_behavior(project: &Project, library: &[(String, String)]) {
+    assert_optimization_keeps_ram(project, library);
+
+    let mut vm = Vm::load_with_library(project.path(), library).unwrap();
+    vm.run_until_halt(MAX_CYCLES).unwrap();
+    let expected = state(|address| vm.ram(address));
+
+    for compact in [false, true] {
+        for optimize in [false, true] {
+            let cpu = run(project, library, &Options { compact, optimize, ..Default::default() });
+            let found = state(|address| cpu.ram(address));
+            let difference = expected.iter().zip(&found).find(|(e, f)| e != f);
+            assert_eq!(difference, None, "compact: {}, optimize: {}", compact, optimize);
+        }
+    }
+}
+
+#[test]
+fn optimized_vm_code_behaves_like_unoptimized() {
+    let project = Project::new("vm", &[("Sys.vm", SYS_VM), ("Main.vm", MAIN_VM)]);
+    assert_same_behavior(&project, &[]);
+
+    let mut vm = Vm::load(project.path()).unwrap();
+    vm.run_until_halt(MAX_CYCLES).unwrap();
+    let this: Vec<u16> = (3000..3008).map(|address| vm.ram(address)).collect();
+    assert_eq!(this, [11, 16, (-4i16) as u16, 4000, 0, 77, 0, 3]);
+    assert_eq!(vm.ram(4005), 22);
+    assert_eq!(vm.ram(12), 22);
+}
+
+#[test]
+fn every_fused_push_pop_pair_behaves_like_unoptimized() {
+    let arguments: String = (0..8).map(|i| format!("push constant {}\n", 50 + i)).collect();
+    let call = format!("{}call Main.pairs 8", arguments);
+    let sys = SYS_VM.replace("push constant 11\npush constant 22\ncall Main.run 2", &call);
+    let project = Project::new("pairs", &[("Sys.vm", &sys), ("Main.vm", &pairs_vm())]);
+    assert_same_behavior(&project, &[]);
+}
+
+#[test]
+fn optimized_jack_program_behaves_like_unoptimized() {
+    let project = Project::new("jack", &[("Main.jack", MAIN_JACK)]);
+    let options = compiler::Options { whole_program: true, ..Default::default() };
+    compiler::analyze_with_options(project.path(), &options).unwrap();
+    let os = compiler::link_os(project.path()).unwrap();
+    assert_same_behavior(&project, &os);
+}
@@ -0,0 +1,103 @@
+// Results of the Jack OS functions, on negative numbers and at the ends of the range
+
+mod common;
+
+use common::Project;
+use hack_emulator::Cpu;
+use hvm::Vm;
+
+const MAX_CYCLES: u64 = 50_000_000;
+
+// Where Main.main writes the results: the screen, which the program does not draw on
+const RESULTS: usize = 16384;
+
+// Writes each result at RESULTS + i
+const MAIN_JACK: &str = r#"
+class Main {
+    function void main() {
+        var Array r;
+        var String s;
+        var int min;
+
+        let r = 16384;
+        let min = -32767 - 1;
+
+        let r[0] = Math.multiply(-3, 7);
+        let r[1] = Math.multiply(-3, -7);
+        let r[2] = 181 * 181;
+        let r[3] = Math.multiply(32767, -1);
+        let r[4] = Math.multiply(min, 1);
+        let r[5] = Math.multiply(0, -5);
+
+        let r[6] = Math.divide(-21, 7);
+        let r[7] = Math.divide(21, -7);
+        let r[8] = Math.divide(-22, -7);
+        let r[9] = Math.divide(7, -22);
+        let r[10] = Math.divide(32767, 2);
+        let r[11] = Math.divide(32767, 32767);
+        let r[12] = Math.divide(-32767, 3);
+        let r[13] = Math.divide(1, 32767);
+
+        let r[14] = Math.sqrt(0);
+        let r[15] = Math.sqrt(2);
+        let r[16] = Math.sqrt(16384);
+        let r[17] = Math.sqrt(32760);
+        let r[18] = Math.sqrt(32761);
+        let r[19] = Math.sqrt(32767);
+
+        let r[20] = Math.max(32767, -2);
+        let r[21] = Math.max(-5, -3);
+        let r[22] = Math.max(min, 32767);
+        let r[23] = Math.min(32767, -2);
+        let r[24] = Math.min(min, 0);
+        let r[25] = Math.min(-5, -3);
+
+        let r[26] = Main.intValue("123");
+        let r[27] = Main.intValue("-456");
+        let r[28] = Main.intValue("32767");
+        let r[29] = Main.intValue("-32767");
+        let r[30] = Main.intValue("12ab3");
+        let r[31] = Main.intValue("-0");
+        let s = String.new(6);
+        do s.setInt(-32767);
+        let r[32] = s.intValue();
+        let r[33] = s.length();
+        return;
+    }
+
+    function int intValue(String s) {
+        return s.intValue();
+    }
+}
+"#;
+
+const EXPECTED: [i16; 34] = [
+    -21, 21, 32761, -32767, -32768, 0, // multiply
+    -3, -3, 3, 0, 16383, 1, -10922, 0, // divide
+    0, 1, 128, 180, 181, 181, // sqrt
+    32767, -3, 32767, -2, -32768, -5, // max, min
+    123, -456, 32767, -32767, 12, 0, -32767, 6, // intValue
+];
+
+fn results(memory: impl Fn(usize) -> u16) -> Vec<i16> {
+    (RESULTS..RESULTS + EXPECTED.len()).map(|address| memory(address) as i16).collect()
+}
+
+#[test]
+fn os_functions_return_the_exact_results() {
+    let project = Project::new("os", &[("Main.jack", MAIN_JACK)]);
+    let options = compiler::Options { whole_program: true, ..Default::default() };
+    compiler::analyze_with_options(project.path(), &options).unwrap();
+    let os = compiler::link_os(project.path()).unwrap();
+
+    let mut vm = Vm::load_with_library(project.path(), &os).unwrap();
+    vm.run_until_halt(MAX_CYCLES).unwrap();
+    assert_eq!(results(|address| vm.ram(address)), EXPECTED);
+
+    let asm = project.file("os.asm");
+    let options = hvm::Options { compact: true, ..Default::default() };
+    hvm::translate_with_options(project.path(), Some(&asm), &os, &options).unwrap();
+    let mut cpu = Cpu::load(&asm).unwrap();
+    cpu.run_until_halt(MAX_CYCLES).unwrap();
+    assert_eq!(results(|address| cpu.ram(address)), EXPECTED);
+}
@@ -0,0 +1,102 @@
+// Screen images: bit 0 of a screen word is its leftmost pixel, in the PPM and the PNG images alike
+
+use hack_emulator::{pixel, render_png, render_ppm, Cpu, RAM_SIZE, SCREEN, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// Black pixels: (0, 0), (15, 0), (17, 0), (1, 1) and (511, 255)
+fn memory() -> Vec<u16> {
+    let mut memory = vec![0; RAM_SIZE];
+    memory[SCREEN] = 0x8001;
+    memory[SCREEN + 1] = 0b10;
+    memory[SCREEN + 32] = 0b10;
+    memory[SCREEN + 32 * 256 - 1] = 0x8000;
+    memory
+}
+
+const BLACK: [(usize, usize); 5] = [(0, 0), (15, 0), (17, 0), (1, 1), (511, 255)];
+
+fn every_pixel() -> impl Iterator<Item = (usize, usize)> {
+    (0..SCREEN_HEIGHT).flat_map(|y| (0..SCREEN_WIDTH).map(move |x| (x, y)))
+}
+
+// Pixels of a 1-bit PNG made of uncompressed deflate blocks
+fn decode_png(image: &[u8]) -> Vec<Vec<bool>> {
+    assert_eq!(&image[..8], b"\x89PNG\r\n\x1a\n");
+    let mut position = 8;
+    let mut zlib = Vec::new();
+    while position < image.len() {
+        let length = u32::from_be_bytes(image[position..position + 4].try_into().unwrap()) as usize;
+        let kind = &image[position + 4..position + 8];
+        let data = &image[position + 8..position + 8 + length];
+        match kind {
+            b"IHDR" => assert_eq!(data, [0, 0, 2, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0]),
+            b"IDAT" => zlib.extend_from_slice(data),
+            _ => {}
+        }
+        position += 12 + length;
+    }
+
+    let mut scanlines = Vec::new();
+    let mut position = 2;
+    loop {
+        let last = zlib[position] & 1 == 1;
+        let length = u16::from_le_bytes([zlib[position + 1], zlib[position + 2]]) as usize;
+        scanlines.extend_from_slice(&zlib[position + 5..position + 5 + length]);
+        position += 5 + length;
+        if last {
+            break;
+        }
+    }
+
+    scanlines
+        .chunks(1 + SCREEN_WIDTH / 8)
+        .map(|line| {
+            assert_eq!(line[0], 0, "filter type");
+            // Grayscale: 0 is black, the leftmost pixel is the most significant bit
+            (0..SCREEN_WIDTH).map(|x| line[1 + x / 8] & (0x80 >> (x % 8)) == 0).collect()
+        })
+        .collect()
+}
+
+#[test]
+fn bit_0_is_the_leftmost_pixel() {
+    let memory = memory();
+    for (x, y) in every_pixel() {
+        assert_eq!(pixel(&memory, x, y), BLACK.contains(&(x, y)), "({}, {})", x, y);
+    }
+}
+
+#[test]
+fn ppm_pixels() {
+    let image = render_ppm(&memory());
+    let header = b"P6\n512 256\n255\n";
+    assert_eq!(&image[..header.len()], header);
+    assert_eq!(image.len(), header.len() + SCREEN_WIDTH * SCREEN_HEIGHT * 3);
+
+    let pixels = &image[header.len()..];
+    for (x, y) in every_pixel() {
+        let color = if BLACK.contains(&(x, y)) { 0 } else { 255 };
+        let offset = (y * SCREEN_WIDTH + x) * 3;
+        assert_eq!(pixels[offset..offset + 3], [color; 3], "({}, {})", x, y);
+    }
+}
+
+#[test]
+fn png_pixels() {
+    let pixels = decode_png(&render_png(&memory()));
+    assert_eq!(pixels.len(), SCREEN_HEIGHT);
+    for (x, y) in every_pixel() {
+        assert_eq!(pixels[y][x], BLACK.contains(&(x, y)), "({}, {})", x, y);
+    }
+}
+
+#[test]
+fn program_draws_where_the_screen_shows() {
+    // RAM[SCREEN] = 1, RAM[SCREEN + 33] = -1: the first pixel of row 0, pixels 16 to 31 of row 1
+    let rom = hack_assembler::assemble_str("@SCREEN\nM=1\n@SCREEN\nD=A\n@33\nA=D+A\nM=-1\n").unwrap();
+    let mut cpu = Cpu::new(rom).unwrap();
+    cpu.run(7);
+    let pixels = decode_png(&render_png(cpu.memory()));
+    let black: Vec<(usize, usize)> = every_pixel().filter(|&(x, y)| pixels[y][x]).collect();
+    let expected: Vec<(usize, usize)> = [(0, 0)].into_iter().chain((16..32).map(|x| (x, 1))).collect();
+    assert_eq!(black, expected);
+}
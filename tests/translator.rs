@@ -0,0 +1,152 @@
+// The translated programs run on the Hack CPU like on the VM emulator
+
+mod common;
+
+use common::Project;
+use hack_emulator::Cpu;
+use hvm::{Options, Vm};
+
+const MAX_CYCLES: u64 = 1_000_000;
+
+// Translates the project with `options` and runs it until it halts
+fn run(project: &Project, options: &Options) -> Cpu {
+    let asm = project.file(&format!("{}{}.asm", options.compact, options.optimize));
+    hvm::translate_with_options(project.path(), Some(&asm), &[], options).unwrap();
+    let mut cpu = Cpu::load(&asm).unwrap();
+    cpu.run_until_halt(MAX_CYCLES).unwrap();
+    cpu
+}
+
+// Main.dirty leaves values on the stack above SP, where the locals of Main.count are pushed
+const LOCALS_VM: &str = "
+function Sys.init 0
+call Main.dirty 0
+pop temp 0
+call Main.count 0
+pop temp 1
+label HALT
+goto HALT
+
+function Main.dirty 3
+push constant 1000
+pop local 0
+push constant 2000
+pop local 1
+push constant 3000
+pop local 2
+push constant 0
+return
+
+// Counts its locals that are 0
+function Main.count 3
+push constant 0
+push local 0
+push constant 0
+eq
+sub
+push local 1
+push constant 0
+eq
+sub
+push local 2
+push constant 0
+eq
+sub
+return
+";
+
+#[test]
+fn function_locals_start_at_zero() {
+    let project = Project::new("locals", &[("Sys.vm", LOCALS_VM)]);
+    for compact in [false, true] {
+        for optimize in [false, true] {
+            let options = Options { compact, optimize, ..Default::default() };
+            let cpu = run(&project, &options);
+            assert_eq!(cpu.ram(6), 3, "{:?}", options);
+        }
+    }
+}
+
+// x - y overflows when x and y are more than 32767 apart, gt and lt still compare the values
+const COMPARE_VM: &str = "
+function Sys.init 0
+push constant 32767
+push constant 2
+neg
+gt
+pop temp 0
+push constant 2
+neg
+push constant 32767
+lt
+pop temp 1
+push constant 32767
+neg
+push constant 1
+sub
+push constant 1
+gt
+pop temp 2
+push constant 32767
+push constant 2
+neg
+lt
+pop temp 3
+push constant 5
+push constant 3
+gt
+pop temp 4
+push constant 3
+push constant 5
+lt
+pop temp 5
+push constant 7
+push constant 7
+lt
+pop temp 6
+push constant 5
+neg
+push constant 3
+neg
+lt
+pop temp 7
+label HALT
+goto HALT
+";
+
+#[test]
+fn comparisons_do_not_overflow() {
+    let project = Project::new("compare", &[("Sys.vm", COMPARE_VM)]);
+    let expected = [-1, -1, 0, 0, -1, -1, 0, -1];
+
+    let mut vm = Vm::load(project.path()).unwrap();
+    vm.run_until_halt(MAX_CYCLES).unwrap();
+    let found: Vec<i16> = (5..13).map(|address| vm.ram(address) as i16).collect();
+    assert_eq!(found, expected);
+
+    for compact in [false, true] {
+        for optimize in [false, true] {
+            let options = Options { compact, optimize, ..Default::default() };
+            let cpu = run(&project, &options);
+            let found: Vec<i16> = (5..13).map(|address| cpu.ram(address) as i16).collect();
+            assert_eq!(found, expected, "{:?}", options);
+        }
+    }
+}
+
+#[test]
+fn rom_usage_is_only_reported_on_request() {
+    let project = Project::new("usage", &[("Sys.vm", COMPARE_VM)]);
+    let asm = project.file("usage.asm");
+    let translate = |options: &Options| hvm::translate_with_options(project.path(), Some(&asm), &[], options).unwrap();
+
+    assert_eq!(translate(&Options::default()), None);
+    let inline = Cpu::load(&asm).unwrap().rom().len();
+    assert_eq!(translate(&Options { compact: true, ..Default::default() }), None);
+    let compact = Cpu::load(&asm).unwrap().rom().len();
+
+    for compact_mode in [false, true] {
+        let usage = translate(&Options { compact: compact_mode, rom_usage: true, ..Default::default() });
+        assert_eq!(usage, Some(hvm::RomUsage { inline, compact }));
+    }
+}
@@ -0,0 +1,124 @@
+// Every HVMError of the VM parser, translator and emulator, with its `file:line: message` text
+
+mod common;
+
+use std::path::Path;
+
+use common::Project;
+use hvm::{HVMError, Vm};
+
+// Messages of the errors of `source` parsed as `Main.vm`
+fn parse_errors(source: &str) -> Vec<String> {
+    let errors = hvm::parse_source("Main.vm", source).unwrap_err();
+    errors.iter().map(HVMError::to_string).collect()
+}
+
+// Messages of the errors of the translation of a folder with `Main.vm`, which writes nothing
+fn translate_errors(name: &str, source: &str) -> (Project, Vec<String>) {
+    let project = Project::new(name, &[("Main.vm", source)]);
+    let errors = hvm::translate(project.path()).unwrap_err();
+    assert!(!Path::new(&project.file(&format!("{}.asm", name))).exists());
+    let messages = errors.iter().map(HVMError::to_string).collect();
+    (project, messages)
+}
+
+#[test]
+fn io() {
+    let errors = hvm::translate("no/such/folder").unwrap_err();
+    assert_eq!(errors[0].to_string(), "no/such/folder: Invalid source path");
+}
+
+#[test]
+fn unknown_command() {
+    assert_eq!(parse_errors("push constant 1\nfoo 1\n"), ["Main.vm:2: unknown VM command `foo 1`"]);
+}
+
+#[test]
+fn wrong_argument_count() {
+    assert_eq!(
+        parse_errors("push constant\nadd 1\ngoto\n"),
+        [
+            "Main.vm:1: `push constant`, push expects 2 arguments",
+            "Main.vm:2: `add 1`, add expects 0 arguments",
+            "Main.vm:3: `goto`, goto expects 1 argument",
+        ]
+    );
+}
+
+#[test]
+fn unknown_segment() {
+    assert_eq!(parse_errors("push heap 1 // comment\n"), ["Main.vm:1: unknown memory segment in `push heap 1`"]);
+}
+
+#[test]
+fn invalid_number() {
+    assert_eq!(
+        parse_errors("push local x\nfunction Main.main -1\n"),
+        ["Main.vm:1: invalid number in `push local x`", "Main.vm:2: invalid number in `function Main.main -1`"]
+    );
+}
+
+#[test]
+fn index_out_of_range() {
+    assert_eq!(
+        parse_errors("pop temp 8\npush pointer 2\n"),
+        [
+            "Main.vm:1: index out of range in `pop temp 8` (the segment has 8 words)",
+            "Main.vm:2: index out of range in `push pointer 2` (the segment has 2 words)",
+        ]
+    );
+}
+
+#[test]
+fn pop_constant() {
+    assert_eq!(
+        parse_errors("\n\npop constant 1\n"),
+        ["Main.vm:3: `pop constant 1` pops into the constant segment, which is read-only"]
+    );
+}
+
+#[test]
+fn unknown_label() {
+    // LOOP is a label of Main.main, not of Main.other
+    let source = "function Main.main 0\nlabel LOOP\nif-goto NOWHERE\ngoto LOOP\nfunction Main.other 0\ngoto LOOP\n";
+    let (project, errors) = translate_errors("labels", source);
+    let file = project.file("Main.vm");
+    assert_eq!(
+        errors,
+        [format!("{}:3: unknown label in `if-goto NOWHERE`", file), format!("{}:6: unknown label in `goto LOOP`", file)]
+    );
+}
+
+#[test]
+fn unknown_function() {
+    let source = "function Main.main 0\ncall Main.main 0\ncall Nope.f 0\nreturn\n";
+    let (project, errors) = translate_errors("functions", source);
+    assert_eq!(errors, [format!("{}:3: unknown function in `call Nope.f 0`", project.file("Main.vm"))]);
+
+    // The VM emulator loads programs the same way
+    let errors = Vm::load(project.path()).unwrap_err();
+    assert_eq!(errors[0].to_string(), format!("{}:3: unknown function in `call Nope.f 0`", project.file("Main.vm")));
+}
+
+#[test]
+fn timeout() {
+    // Counts forever: the RAM never comes back to the same state
+    let source = "function Sys.init 0\nlabel LOOP\npush temp 0\npush constant 1\nadd\npop temp 0\ngoto LOOP\n";
+    let project = Project::new("timeout", &[("Sys.vm", source)]);
+    let mut vm = Vm::load(project.path()).unwrap();
+    let error = vm.run_until_halt(100).unwrap_err();
+    assert_eq!(error.to_string(), "program did not halt after 100 commands");
+}
+
+#[test]
+fn errors_of_every_file_are_reported() {
+    let project = Project::new("files", &[("Main.vm", "push local\n"), ("Sys.vm", "pop constant 0\n")]);
+    let errors: Vec<String> = hvm::translate(project.path()).unwrap_err().iter().map(HVMError::to_string).collect();
+    assert_eq!(
+        errors,
+        [
+            format!("{}:1: `push local`, push expects 2 arguments", project.file("Main.vm")),
+            format!("{}:1: `pop constant 0` pops into the constant segment, which is read-only", project.file("Sys.vm")),
+        ]
+    );
+}
@@ -0,0 +1,94 @@
+// Official nand2tetris test scripts of projects 7 and 8: the CPU scripts run the translated
+// programs, the VME scripts run the VM code on the VM emulator, both against the same `.cmp`.
+// The scripts set SP and the segments themselves and check the RAM after a number of steps.
+
+mod common;
+
+use common::Project;
+use hvm::Options;
+
+// Translates the folder `name` and runs its CPU script in every translation mode
+fn assert_cpu_script_passes(name: &str) {
+    for compact in [false, true] {
+        for optimize in [false, true] {
+            let options = Options { compact, optimize, ..Default::default() };
+            let fixture = Project::fixture(name);
+            hvm::translate_with_options(fixture.path(), None, &[], &options).unwrap();
+            let outcome = hack_emulator::run_test_script(&fixture.file(&format!("{}.tst", name))).unwrap();
+            assert_eq!(outcome.failure, None, "{}, {:?}", name, options);
+        }
+    }
+}
+
+// Runs the VME script of the folder `name`
+fn assert_vme_script_passes(name: &str) {
+    let fixture = Project::fixture(name);
+    let outcome = hack_emulator::run_test_script(&fixture.file(&format!("{}VME.tst", name))).unwrap();
+    assert_eq!(outcome.failure, None, "{}", name);
+}
+
+#[test]
+fn project_7_simple_add() {
+    assert_cpu_script_passes("SimpleAdd");
+    assert_vme_script_passes("SimpleAdd");
+}
+
+#[test]
+fn project_7_stack_test() {
+    assert_cpu_script_passes("StackTest");
+    assert_vme_script_passes("StackTest");
+}
+
+#[test]
+fn project_7_basic_test() {
+    assert_cpu_script_passes("BasicTest");
+    assert_vme_script_passes("BasicTest");
+}
+
+#[test]
+fn project_7_pointer_test() {
+    assert_cpu_script_passes("PointerTest");
+    assert_vme_script_passes("PointerTest");
+}
+
+#[test]
+fn project_7_static_test() {
+    assert_cpu_script_passes("StaticTest");
+    assert_vme_script_passes("StaticTest");
+}
+
+#[test]
+fn project_8_basic_loop() {
+    assert_cpu_script_passes("BasicLoop");
+    assert_vme_script_passes("BasicLoop");
+}
+
+#[test]
+fn project_8_fibonacci_series() {
+    assert_cpu_script_passes("FibonacciSeries");
+    assert_vme_script_passes("FibonacciSeries");
+}
+
+#[test]
+fn project_8_simple_function() {
+    assert_cpu_script_passes("SimpleFunction");
+    assert_vme_script_passes("SimpleFunction");
+}
+
+#[test]
+fn project_8_nested_call() {
+    assert_cpu_script_passes("NestedCall");
+    assert_vme_script_passes("NestedCall");
+}
+
+#[test]
+fn project_8_fibonacci_element() {
+    assert_cpu_script_passes("FibonacciElement");
+    assert_vme_script_passes("FibonacciElement");
+}
+
+#[test]
+fn project_8_statics_test() {
+    assert_cpu_script_passes("StaticsTest");
+    assert_vme_script_passes("StaticsTest");
+}